@@ -1,10 +1,15 @@
 extern crate fbp_rs;
 
 use fbp_rs::{
-    blueprint::{read_blueprint, Grid}, entities::EntityShape,
+    blueprint::{read_blueprint, Grid},
+    entities::{EntityShape, Prototypes},
 };
 use std::{
-    env, fs::File, io::{prelude::*, BufReader}, path::Path,
+    env,
+    fs::{self, File},
+    io::{prelude::*, BufReader},
+    path::Path,
+    process,
 };
 
 fn main() {
@@ -14,6 +19,7 @@ fn main() {
 
     // If invoked with no args, use balancer.txt
     // Otherwise attempt to use the first arg
+    // A second arg is a data.raw dump to use instead of the vanilla prototypes
     let bp_string = if args.len() == 1 {
         println!("*** No argument given, using balancer.txt ***");
         let bp_file =
@@ -28,18 +34,47 @@ fn main() {
         args[1].clone()
     };
 
-    let parsed_bp = read_blueprint(&bp_string).unwrap();
-    let shapes: Vec<Result<EntityShape, String>> = parsed_bp
-        .blueprint
-        .entities
+    let parsed_bp = match read_blueprint(&bp_string) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Could not read blueprint: {}", e);
+            process::exit(1);
+        }
+    };
+    let modded;
+    let prototypes = match args.get(2) {
+        Some(path) => {
+            let loaded = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| Prototypes::from_data_raw(&json).map_err(|e| e.to_string()));
+            modded = match loaded {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("Could not load prototypes from {}: {}", path, e);
+                    process::exit(1);
+                }
+            };
+            &modded
+        }
+        None => Prototypes::vanilla(),
+    };
+    // books get flattened - every blueprint inside contributes its entities
+    let shapes: Vec<fbp_rs::Result<EntityShape>> = parsed_bp
+        .blueprints()
         .iter()
-        .map(EntityShape::from)
+        .flat_map(|bp| {
+            let system = bp.direction_system();
+            bp.entities
+                .iter()
+                .map(move |e| EntityShape::with_prototypes(e, system, prototypes))
+        })
         .collect();
     println!("{:#?}", shapes);
-    //let grid = Grid::from(parsed_bp).unwrap();
 
-    //   println!(
-    //      "fbp-tool\n--------\ninput string:\n{}\n\npreview:\n{}",
-    //      bp_string, grid
-    //  );
+    for bp in parsed_bp.blueprints() {
+        match Grid::with_prototypes(bp, prototypes) {
+            Ok(grid) => println!("preview:\n{}", grid),
+            Err(e) => eprintln!("No preview: {}", e),
+        }
+    }
 }
@@ -1,176 +1,324 @@
 use base64::{decode, encode};
+use entities::Prototypes;
+use error::{Error, Result};
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::de;
 use serde_json;
+use serde_path_to_error;
 use std::{
-    fmt, io::{self, prelude::*},
+    collections::{HashMap, HashSet},
+    fmt,
+    io::prelude::*,
 };
 use types::*;
 
-// Grid type to coerce the entity list into
-// TODO should this move over into types.rs?  'tis a type
-// you've really gotta get a hold on your project structures, you do something slightly different each time
-// here, types should probably be blueprint.rs and this should be like, render.rs or something
-// move al thedecode/deserialze/read fns over there too
-
-// NOTE: The Display impl on Grid isnt supposed to be the grand finale, ehre
-// I haven't done anything abotu shapes and sizes yet, which is pretty crucial
-// TODO separate cell type, with its own display, that comes out as a square
+// Where everything in a blueprint sits, tile by tile
+// Only tiles with something on them are stored, so a sprawling base costs no more than its entities
+// Multi-tile entities are registered on every tile of their footprint
 
 #[derive(Debug)]
 pub struct Grid {
-    // A 2D grid of cells, each of which can hold multiple entities
-    pub cells: Vec<Vec<Vec<Entity>>>,
+    entities: Vec<Entity>,
+    footprints: Vec<TileRect>,              // one per entity
+    tiles: HashMap<(i32, i32), Vec<usize>>, // indices into entities, in blueprint order
+    by_number: HashMap<i32, usize>,
+    bounds: TileRect,
 }
 
 impl Grid {
-    // TODO real error
-    pub fn from(c: Container) -> Result<Self, String> {
-        let bp = c.blueprint;
-        let size = bp.size();
-        let entities = bp.entities;
-        let mut cells = vec![vec![vec![]; size]; size];
-        for e in &entities {
-            // replace the proper cell with the entity
-            let pos = &e.position;
-            // get the top left corner oriented coords from the center-oriented coords
-            let (grid_x, grid_y) = pos.grid_coords(size); // this has f64 coords, truncate here
-                                                          // you have a problem with overlaps - maybe store a Vec
-            cells[grid_x][grid_y].push(e.clone());
-        }
-        Ok(Grid { cells })
-    }
-
-    pub fn max_cell_len(&self) -> usize {
-        let mut max = 0;
-        for line in &self.cells {
-            for cell in line.iter() {
-                let cell_len = cell.len();
-                if cell_len > max {
-                    max = cell_len;
-                }
+    pub fn from(c: Container) -> Result<Self> {
+        match c {
+            Container::Blueprint(bp) => Grid::new(&bp),
+            _ => Err(Error::NotABlueprint),
+        }
+    }
+
+    pub fn new(bp: &Blueprint) -> Result<Self> {
+        Grid::with_prototypes(bp, Prototypes::vanilla())
+    }
+
+    pub fn with_prototypes(bp: &Blueprint, prototypes: &Prototypes) -> Result<Self> {
+        let system = bp.direction_system();
+        let mut grid = Grid {
+            entities: Vec::with_capacity(bp.entities.len()),
+            footprints: Vec::with_capacity(bp.entities.len()),
+            tiles: HashMap::new(),
+            by_number: HashMap::new(),
+            bounds: TileRect::default(),
+        };
+        for e in &bp.entities {
+            let footprint = prototypes.footprint(e, system)?;
+            let idx = grid.entities.len();
+            for tile in footprint.tiles() {
+                grid.tiles.entry(tile).or_default().push(idx);
             }
+            grid.bounds = grid.bounds.union(&footprint);
+            grid.by_number.insert(e.entity_number, idx);
+            grid.footprints.push(footprint);
+            grid.entities.push(e.clone());
         }
-        max
+        Ok(grid)
+    }
+
+    // covers every entity, but not the blueprint's tiles
+    pub fn bounds(&self) -> TileRect {
+        self.bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn entity(&self, entity_number: i32) -> Option<&Entity> {
+        self.by_number
+            .get(&entity_number)
+            .map(|&i| &self.entities[i])
     }
 
-    pub fn max_line_len(&self) -> usize {
-        let mut max = 0;
-        for line in &self.cells {
-            let mut line_max_len = line.len();
-            for cell in line.iter() {
-                let cell_len = cell.len();
-                if cell_len > line_max_len {
-                    line_max_len = cell_len;
+    pub fn footprint(&self, entity_number: i32) -> Option<TileRect> {
+        self.by_number
+            .get(&entity_number)
+            .map(|&i| self.footprints[i])
+    }
+
+    // everything on tile (x, y) - usually one thing, but overlaps are kept
+    pub fn at(&self, x: i32, y: i32) -> Vec<&Entity> {
+        self.tiles
+            .get(&(x, y))
+            .map(|v| v.iter().map(|&i| &self.entities[i]).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        self.tiles.contains_key(&(x, y))
+    }
+
+    // whatever is on the tiles directly north, east, south and west of (x, y)
+    pub fn neighbours_of_tile(&self, x: i32, y: i32) -> Vec<&Entity> {
+        self.collect(
+            [(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)]
+                .iter()
+                .cloned(),
+            None,
+        )
+    }
+
+    // everything touching an edge of the entity's footprint, not counting itself
+    pub fn neighbours(&self, entity_number: i32) -> Vec<&Entity> {
+        let idx = match self.by_number.get(&entity_number) {
+            Some(&i) => i,
+            None => return Vec::new(),
+        };
+        let f = self.footprints[idx];
+        let above = (f.left..f.right).map(|x| (x, f.top - 1));
+        let below = (f.left..f.right).map(|x| (x, f.bottom));
+        let left = (f.top..f.bottom).map(|y| (f.left - 1, y));
+        let right = (f.top..f.bottom).map(|y| (f.right, y));
+        self.collect(above.chain(right).chain(below).chain(left), Some(idx))
+    }
+
+    // entities on the given tiles, each once, in the order first seen
+    fn collect<I: Iterator<Item = (i32, i32)>>(
+        &self,
+        tiles: I,
+        skip: Option<usize>,
+    ) -> Vec<&Entity> {
+        let mut seen = HashSet::new();
+        let mut ret = Vec::new();
+        for tile in tiles {
+            for &i in self.tiles.get(&tile).into_iter().flatten() {
+                if Some(i) != skip && seen.insert(i) {
+                    ret.push(&self.entities[i]);
                 }
             }
-            if line_max_len > max {
-                max = line_max_len
-            }
         }
-        max
+        ret
+    }
+
+    // occupied tiles top to bottom, left to right within a row
+    pub fn iter(&self) -> impl Iterator<Item = ((i32, i32), Vec<&Entity>)> {
+        let mut keys: Vec<(i32, i32)> = self.tiles.keys().cloned().collect();
+        keys.sort_by_key(|&(x, y)| (y, x));
+        keys.into_iter().map(move |(x, y)| ((x, y), self.at(x, y)))
     }
 }
 
 impl fmt::Display for Grid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // get the max lengths first
-        let max_cell_len = self.max_cell_len() * ENTITY_LEN;
-        let max_line_len = self.max_line_len() * max_cell_len;
-        let mut ret = String::new();
-        for line in &self.cells {
-            let mut line_string = String::from("|");
-            let line_padding_diff = ((max_line_len - line.len() * max_cell_len) / 2) + 1;
-            for _ in 0..line_padding_diff {
-                line_string.push_str(" ");
+        // one column per tile, showing whatever was placed there first
+        let b = self.bounds;
+        for y in b.top..b.bottom {
+            let mut line = String::from("|");
+            for x in b.left..b.right {
+                let cell = match self.at(x, y).first() {
+                    Some(e) => {
+                        let mut name = format!("{}", e);
+                        name.truncate(ENTITY_LEN);
+                        name
+                    }
+                    None => String::new(),
+                };
+                line.push_str(&format!("{:1$}|", cell, ENTITY_LEN));
             }
-            for cell in line.iter() {
-                let mut cell_string = String::from(":c:");
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
 
-                let cell_padding_diff = ((max_cell_len - cell.len() * ENTITY_LEN) / 2) + 1;
-                for _ in 0..cell_padding_diff {
-                    cell_string.push_str(" ");
-                }
-                for entity in cell.iter() {
-                    // ensure its exactly ENTITY_LEN, no more, no less
-                    let mut e = format!("{:1$}", entity, ENTITY_LEN);
-                    e.truncate(ENTITY_LEN);
-                    cell_string.push_str(&format!("{:1$}", e, ENTITY_LEN));
-                }
-                for _ in 0..cell_padding_diff {
-                    cell_string.push_str(" ")
-                }
+// The leading character of a blueprint string, telling us how the rest is encoded
+// Every release from 0.15 through 2.0 writes "0" - anything else is refused rather than guessed at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StringVersion {
+    #[default]
+    V0, // base64 of zlib-deflated JSON
+}
 
-                cell_string.push_str(":c:");
-                line_string.push_str(&cell_string);
-            }
-            for _ in 0..line_padding_diff {
-                line_string.push_str(" ");
-            }
-            line_string.push_str("|\n");
-            ret.push_str(&line_string);
+impl StringVersion {
+    pub fn from_char(c: char) -> Result<Self> {
+        match c {
+            '0' => Ok(StringVersion::V0),
+            _ => Err(Error::UnknownVersion(c)),
         }
-        writeln!(f, "{}", ret)
     }
+
+    pub fn as_char(self) -> char {
+        match self {
+            StringVersion::V0 => '0',
+        }
+    }
+}
+
+// clipboard pastes and files bring along newlines and wrapping - none of it is part of the string
+fn strip_whitespace(bp: &str) -> String {
+    bp.chars().filter(|c| !c.is_whitespace()).collect()
 }
 
-// returns a Json string from the compressed Blueprint
-fn decode_blueprint(bp: &str) -> io::Result<String> {
-    // skip the version byte - it's always "0" in factorio 0.15 and 0.16
-    let encoded = &bp[1..];
+// turns an offset into the stripped string back into one into what the caller gave us
+fn original_offset(bp: &str, stripped_offset: usize) -> usize {
+    bp.char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .nth(stripped_offset)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| bp.len())
+}
+
+// returns the version and a Json string from the compressed Blueprint
+fn decode_blueprint(bp: &str) -> Result<(StringVersion, String)> {
+    let stripped = strip_whitespace(bp);
+
+    // check the version byte before touching the rest
+    let version_char = stripped.chars().next().ok_or(Error::Empty)?;
+    let version = StringVersion::from_char(version_char)?;
+    let encoded = &stripped[version_char.len_utf8()..];
 
     // base64 decode
-    let decoded = decode(encoded).expect("Could not base64 decode blueprint");
+    let decoded = decode(encoded).map_err(|e| {
+        Error::from_base64(
+            e,
+            |offset| original_offset(bp, offset + version_char.len_utf8()),
+            encoded.len(),
+        )
+    })?;
 
     // decompress with zlib deflate
     let mut bp_decoder = ZlibDecoder::new(&decoded[..]);
     let mut json_string = String::new();
     bp_decoder.read_to_string(&mut json_string)?;
-    Ok(json_string)
+    Ok((version, json_string))
 }
 
-fn deserialize_blueprint(json: &str) -> io::Result<Container> {
-    let ret: Container = serde_json::from_str(json).expect("Could not deserialize json");
+// errors carry the JSON path to the offending value, e.g. "blueprint.entities[12].position.x"
+fn deserialize_blueprint(json: &str) -> Result<Container> {
+    let de = &mut serde_json::Deserializer::from_str(json);
+    let ret: Container = serde_path_to_error::deserialize(de)?;
+    check_directions(&ret, "")?;
     Ok(ret)
 }
 
+// A direction number is only known to be bad once we've seen the version at the end of the blueprint
+fn check_directions(c: &Container, prefix: &str) -> Result<()> {
+    match c {
+        Container::Blueprint(bp) => {
+            let system = bp.direction_system();
+            for (i, e) in bp.entities.iter().enumerate() {
+                if let Some(d) = e.direction {
+                    if d.in_system(system).is_none() {
+                        return Err(Error::Json {
+                            path: format!("{}blueprint.entities[{}].direction", prefix, i),
+                            source: de::Error::custom(format!(
+                                "invalid direction {} for a {} blueprint",
+                                d.raw(),
+                                bp.version
+                            )),
+                        });
+                    }
+                }
+            }
+            Ok(())
+        }
+        Container::BlueprintBook(book) => {
+            for (i, entry) in book.blueprints.iter().enumerate() {
+                check_directions(
+                    &entry.content,
+                    &format!("{}blueprint_book.blueprints[{}].", prefix, i),
+                )?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
 // Call decode and then serialize to bring a compressed string to a Rust struct
-pub fn read_blueprint(bp: &str) -> io::Result<Container> {
-    Ok(deserialize_blueprint(&decode_blueprint(bp)?)?)
+pub fn read_blueprint(bp: &str) -> Result<Container> {
+    read_blueprint_versioned(bp).map(|(_, c)| c)
+}
+
+// Same as read_blueprint, but also tells you which string version it was written with
+pub fn read_blueprint_versioned(bp: &str) -> Result<(StringVersion, Container)> {
+    let (version, json) = decode_blueprint(bp)?;
+    Ok((version, deserialize_blueprint(&json)?))
 }
 
-pub fn write_blueprint(c: &Container) -> io::Result<String> {
+// Writes with the version every current release of the game reads
+pub fn write_blueprint(c: &Container) -> Result<String> {
+    write_blueprint_versioned(c, StringVersion::default())
+}
+
+pub fn write_blueprint_versioned(c: &Container, version: StringVersion) -> Result<String> {
     // serialize to JSON
     let raw_json = serde_json::to_string(c)?;
 
-    // compress with zlib
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write(&raw_json.as_bytes())?;
+    // compress with zlib - the game uses the best compression level
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(raw_json.as_bytes())?;
     let compressed = encoder.finish()?;
 
     // base64 encode
     let encoded = encode(&compressed);
 
-    // version byte in front - 0 for factorio 0.15/0.16
-    let ret = format!("0{}", encoded);
+    // version byte in front
+    let ret = format!("{}{}", version.as_char(), encoded);
 
     Ok(ret)
 }
 
 #[cfg(test)]
 mod tests {
+    use fixtures::balancer;
     use std::{fs::File, io::BufReader, path::Path};
+
     #[test]
     fn test_read_blueprint() {
         use super::*;
         // serialize the sample json, and compare it to our version
 
-        let sample_bp_f =
-            File::open(Path::new("./resource/balancer.txt")).expect("Could not open balancer.txt");
-        let mut bp_reader = BufReader::new(sample_bp_f);
-        let mut bp_string = String::new();
-        bp_reader
-            .read_to_string(&mut bp_string)
-            .expect("Could not read balancer.txt");
+        let bp_string = balancer();
 
         let decoded_target_file = File::open(Path::new("./resource/balancer.json"))
             .expect("Could not open balancer.json");
@@ -189,17 +337,206 @@ mod tests {
     fn test_roundtrip_blueprint() {
         use super::*;
 
-        let sample_bp_f =
-            File::open(Path::new("./resource/balancer.txt")).expect("Could not open balancer.txt");
-        let mut bp_reader = BufReader::new(sample_bp_f);
-        let mut bp_string = String::new();
-        bp_reader
-            .read_to_string(&mut bp_string)
-            .expect("Could not read balancer.txt");
+        let bp_string = balancer();
 
         assert_eq!(
             bp_string,
             write_blueprint(&read_blueprint(&bp_string).unwrap()).unwrap()
         )
     }
+    #[test]
+    fn test_roundtrip_nested_book() {
+        use super::*;
+
+        let bp_string = balancer();
+        let balancer = read_blueprint(&bp_string).unwrap();
+
+        let inner = Container::BlueprintBook(BlueprintBook {
+            blueprints: vec![BookEntry {
+                content: balancer.clone(),
+                index: 0,
+                extra: Extra::new(),
+            }],
+            item: "blueprint-book".into(),
+            label: Some("inner".into()),
+            icons: None,
+            active_index: 0,
+            version: MapVersion::from(68722819072),
+            extra: Extra::new(),
+        });
+        let outer = Container::BlueprintBook(BlueprintBook {
+            blueprints: vec![
+                BookEntry {
+                    content: balancer,
+                    index: 0,
+                    extra: Extra::new(),
+                },
+                BookEntry {
+                    content: inner,
+                    index: 1,
+                    extra: Extra::new(),
+                },
+            ],
+            item: "blueprint-book".into(),
+            label: Some("outer".into()),
+            icons: None,
+            active_index: 1,
+            version: MapVersion::from(68722819072),
+            extra: Extra::new(),
+        });
+
+        let book_string = write_blueprint(&outer).unwrap();
+        let parsed = read_blueprint(&book_string).unwrap();
+        assert_eq!(parsed.blueprints().len(), 2);
+        assert_eq!(parsed, outer);
+        assert_eq!(book_string, write_blueprint(&parsed).unwrap())
+    }
+    #[test]
+    fn test_book_json_preserved() {
+        use super::*;
+
+        let json = r#"{"blueprint_book":{"blueprints":[{"blueprint":{"icons":[{"signal":{"type":"item","name":"transport-belt"},"index":1}],"entities":[{"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":-0.5}}],"item":"blueprint","version":68722819072},"index":0},{"blueprint_book":{"blueprints":[],"item":"blueprint-book","active_index":0,"version":68722819072},"index":1}],"item":"blueprint-book","label":"books","active_index":0,"version":68722819072}}"#;
+        let book = deserialize_blueprint(json).unwrap();
+        assert_eq!(book.label(), Some("books"));
+        assert_eq!(serde_json::to_string(&book).unwrap(), json)
+    }
+    #[test]
+    fn test_unknown_version_byte() {
+        use super::*;
+
+        match read_blueprint("1eNqrVkrKKU0tKEosyS9SsopWKs4sSc1VKMksSc1VqI0FAPVzDQg=") {
+            Err(Error::UnknownVersion('1')) => (),
+            other => panic!("expected UnknownVersion, got {:?}", other),
+        }
+        match read_blueprint("") {
+            Err(Error::Empty) => (),
+            other => panic!("expected Empty, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_bad_base64_offset() {
+        use super::*;
+
+        match read_blueprint("0eNqr*Vkr") {
+            Err(Error::InvalidBase64 {
+                offset: 5,
+                byte: b'*',
+            }) => (),
+            other => panic!("expected InvalidBase64, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_truncated_zlib() {
+        use super::*;
+
+        let bp_string = balancer();
+
+        // cut it off on a base64 quad boundary so only the zlib stream is short
+        let cut = 1 + (bp_string.len() - 1) / 2 / 4 * 4;
+        match read_blueprint(&bp_string[..cut]) {
+            Err(Error::Truncated) => (),
+            other => panic!("expected Truncated, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_json_error_path() {
+        use super::*;
+
+        let json = r#"{"blueprint":{"icons":[],"entities":[{"entity_number":1,"name":"transport-belt","position":{"x":"left","y":0}}],"item":"blueprint","version":0}}"#;
+        match deserialize_blueprint(json) {
+            Err(Error::Json { path, .. }) => assert_eq!(path, "blueprint.entities[0].position.x"),
+            other => panic!("expected Json, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_read_blueprint_with_whitespace() {
+        use super::*;
+
+        let bp_string = balancer();
+        let target = read_blueprint(&bp_string).unwrap();
+
+        // a trailing newline, as any editor would save it
+        assert_eq!(read_blueprint(&format!("{}\n", bp_string)).unwrap(), target);
+        // CRLF padding and hard-wrapped lines
+        let wrapped: Vec<String> = bp_string
+            .as_bytes()
+            .chunks(76)
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect();
+        let wrapped = format!("  {}\r\n", wrapped.join("\r\n"));
+        let (version, parsed) = read_blueprint_versioned(&wrapped).unwrap();
+        assert_eq!(version, StringVersion::V0);
+        assert_eq!(parsed, target);
+        assert_eq!(
+            write_blueprint_versioned(&parsed, StringVersion::V0).unwrap(),
+            bp_string
+        );
+    }
+    #[test]
+    fn test_bad_base64_offset_with_whitespace() {
+        use super::*;
+
+        // offset points at the '*' in what we were given, not the stripped copy
+        match read_blueprint("\n0eNq\nr*Vkr") {
+            Err(Error::InvalidBase64 {
+                offset: 7,
+                byte: b'*',
+            }) => (),
+            other => panic!("expected InvalidBase64, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_direction_checked_against_version() {
+        use super::*;
+
+        // 9 is fine in 2.0, but there's no such direction in 0.16
+        let json = r#"{"blueprint":{"icons":[],"entities":[{"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":0.5},"direction":9}],"item":"blueprint","version":68722819072}}"#;
+        match deserialize_blueprint(json) {
+            Err(Error::Json { path, .. }) => assert_eq!(path, "blueprint.entities[0].direction"),
+            other => panic!("expected Json, got {:?}", other),
+        }
+        let json = json.replace("68722819072", "562949954076673");
+        assert!(deserialize_blueprint(&json).is_ok());
+    }
+    #[test]
+    fn test_planners_in_book_roundtrip() {
+        use super::*;
+
+        let json = r#"{"blueprint_book":{"blueprints":[{"upgrade_planner":{"settings":{"mappers":[{"from":{"type":"entity","name":"inserter"},"to":{"type":"entity","name":"fast-inserter"},"index":0}]},"item":"upgrade-planner","version":281479275675648},"index":0},{"deconstruction_planner":{"settings":{"trees_and_rocks_only":true},"item":"deconstruction-planner","version":281479275675648},"index":1}],"item":"blueprint-book","active_index":0,"version":281479275675648}}"#;
+        let book = deserialize_blueprint(json).unwrap();
+        let book_string = write_blueprint(&book).unwrap();
+        let parsed = read_blueprint(&book_string).unwrap();
+        assert_eq!(parsed, book);
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_grid_footprints() {
+        use super::*;
+
+        let json = r#"{"blueprint":{"entities":[{"entity_number":1,"name":"assembling-machine-1","position":{"x":-1.5,"y":-1.5}},{"entity_number":2,"name":"inserter","position":{"x":0.5,"y":-1.5},"direction":6},{"entity_number":3,"name":"splitter","position":{"x":1.5,"y":-1},"direction":2},{"entity_number":4,"name":"transport-belt","position":{"x":-1.5,"y":0.5}}],"item":"blueprint","version":281479275675648}}"#;
+        let grid = Grid::from(deserialize_blueprint(json).unwrap()).unwrap();
+        assert_eq!(grid.len(), 4);
+        assert_eq!(grid.bounds(), TileRect::new(-3, -3, 2, 1));
+
+        // the assembler is on all nine of its tiles
+        for (x, y) in TileRect::new(-3, -3, 0, 0).tiles() {
+            assert_eq!(grid.at(x, y)[0].entity_number, 1);
+        }
+        // and the east facing splitter on both of its
+        assert_eq!(grid.at(1, -2)[0].entity_number, 3);
+        assert_eq!(grid.at(1, -1)[0].entity_number, 3);
+        assert!(grid.at(5, 5).is_empty());
+
+        let numbers = |v: Vec<&Entity>| v.iter().map(|e| e.entity_number).collect::<Vec<_>>();
+        assert_eq!(numbers(grid.neighbours(1)), vec![2, 4]);
+        assert_eq!(numbers(grid.neighbours(2)), vec![3, 1]);
+        assert_eq!(numbers(grid.neighbours_of_tile(0, -1)), vec![2, 3, 1]);
+
+        // row order - top to bottom, then left to right
+        let order: Vec<(i32, i32)> = grid.iter().map(|(tile, _)| tile).collect();
+        assert_eq!(order.len(), 9 + 1 + 2 + 1);
+        assert_eq!(&order[..4], &[(-3, -3), (-2, -3), (-1, -3), (-3, -2)]);
+        assert_eq!(order[order.len() - 1], (-2, 0));
+    }
 }
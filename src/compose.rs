@@ -0,0 +1,830 @@
+// Building blueprints out of other blueprints
+// Unlike transform these hand back a new blueprint and leave the ones passed in alone
+
+use entities::{EntityCategory, Prototypes};
+use error::{Error, Result};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+};
+use types::{
+    Blueprint, Connection, ConnectionData, ConnectionPoint, Entity, Extra, Fixed, Position, Schema,
+    TileRect, Wire, WireConnector, WireEnd,
+};
+use validate::{validate_collisions_with, CollisionIssue};
+
+pub fn merge(
+    a: &Blueprint,
+    b: &Blueprint,
+    offset: Position,
+) -> Result<(Blueprint, Vec<CollisionIssue>)> {
+    merge_with(a, b, offset, Prototypes::vanilla())
+}
+
+// One blueprint with everything from a, and everything from b moved over by offset
+// b's entities are numbered on from a's, with their wires and schedules following along
+// The label, icons and parameters are a's. Where both have a tile in the same spot b's is kept
+// The issues are whatever the merge caused that a didn't already have - b landing on a, or
+// an offset that isn't a whole number of tiles (or rail cells)
+pub fn merge_with(
+    a: &Blueprint,
+    b: &Blueprint,
+    offset: Position,
+    prototypes: &Prototypes,
+) -> Result<(Blueprint, Vec<CollisionIssue>)> {
+    if a.schema() != b.schema() {
+        return Err(Error::SchemaMismatch(a.schema(), b.schema()));
+    }
+    let mut ret = a.clone();
+    ret.version = cmp::max(a.version, b.version);
+    append(&mut ret, b, offset, last_number(a));
+
+    let before = validate_collisions_with(a, prototypes)?;
+    let issues = validate_collisions_with(&ret, prototypes)?
+        .into_iter()
+        .filter(|i| !before.contains(i))
+        .collect();
+    dedup_tiles(&mut ret);
+    Ok((ret, issues))
+}
+
+// Which wires to run across the seams between one copy and the next
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeamWiring {
+    pub copper: bool,
+    pub circuits: bool, // only colours both poles already have something on
+}
+
+pub fn tile(
+    bp: &Blueprint,
+    count_x: u32,
+    count_y: u32,
+    spacing: i32,
+) -> Result<(Blueprint, Vec<CollisionIssue>)> {
+    tile_wired(bp, count_x, count_y, spacing, SeamWiring::default())
+}
+
+pub fn tile_wired(
+    bp: &Blueprint,
+    count_x: u32,
+    count_y: u32,
+    spacing: i32,
+    wiring: SeamWiring,
+) -> Result<(Blueprint, Vec<CollisionIssue>)> {
+    tile_with(bp, count_x, count_y, spacing, wiring, Prototypes::vanilla())
+}
+
+// count_x by count_y copies of bp, spacing tiles apart (so 0 is butted right up against each other)
+// With rails in bp the gap grows by a tile wherever it's needed to keep them on their grid
+// Copy k, counting along the rows, has entity_number n + k * the highest number in bp
+// Each seam gets the nearest pair of poles across it that are in reach of each other, if there is
+// one. The issues are everything wrong with the result, including whatever was already wrong with bp
+pub fn tile_with(
+    bp: &Blueprint,
+    count_x: u32,
+    count_y: u32,
+    spacing: i32,
+    wiring: SeamWiring,
+    prototypes: &Prototypes,
+) -> Result<(Blueprint, Vec<CollisionIssue>)> {
+    let bounds = bp.bounding_box_with(prototypes)?;
+    let (mut step_x, mut step_y) = (
+        bounds.width() as i32 + spacing,
+        bounds.height() as i32 + spacing,
+    );
+    // rails only go on a 2x2 grid, so every copy has to be a whole number of rail cells over
+    let has_rails = bp.entities.iter().any(|e| {
+        prototypes
+            .get(&e.name)
+            .is_some_and(|p| p.category == EntityCategory::Rail)
+    });
+    if has_rails {
+        step_x += step_x.rem_euclid(2);
+        step_y += step_y.rem_euclid(2);
+    }
+    let per_copy = last_number(bp);
+    let (count_x, count_y) = (count_x as i32, count_y as i32);
+    let copy = |x: i32, y: i32| y * count_x + x;
+
+    let mut ret = bp.clone();
+    ret.entities.clear();
+    ret.wires.clear();
+    ret.schedules.clear();
+    ret.stock_connections.clear();
+    ret.tiles = None;
+    for y in 0..count_y {
+        for x in 0..count_x {
+            let offset = Position {
+                x: Fixed::from_tiles(x * step_x),
+                y: Fixed::from_tiles(y * step_y),
+            };
+            append(&mut ret, bp, offset, copy(x, y) * per_copy);
+        }
+    }
+
+    // every copy is the same, so one pair of poles does for every seam running the same way
+    let wires = bp.normalized_wires();
+    let poles: Vec<(&Entity, f64)> = bp
+        .entities
+        .iter()
+        .filter_map(|e| prototypes.get(&e.name)?.wire_reach.map(|reach| (e, reach)))
+        .collect();
+    let mut connectors = Vec::new();
+    if wiring.copper {
+        connectors.push(WireConnector::PoleCopper);
+    }
+    if wiring.circuits {
+        connectors.push(WireConnector::CircuitRed);
+        connectors.push(WireConnector::CircuitGreen);
+    }
+    let mut seams = Vec::new();
+    for &(dx, dy) in &[(1, 0), (0, 1)] {
+        let shift = (f64::from(dx * step_x), f64::from(dy * step_y));
+        for &connector in &connectors {
+            let carries = |e: &Entity| {
+                connector == WireConnector::PoleCopper
+                    || wires
+                        .iter()
+                        .any(|w| [w.from, w.to].contains(&WireEnd::new(e.entity_number, connector)))
+            };
+            let (a, b) = match nearest_pair(&poles, shift, carries) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            for y in 0..count_y - dy {
+                for x in 0..count_x - dx {
+                    seams.push(Wire::new(
+                        WireEnd::new(a + copy(x, y) * per_copy, connector),
+                        WireEnd::new(b + copy(x + dx, y + dy) * per_copy, connector),
+                    ));
+                }
+            }
+        }
+    }
+    add_wires(&mut ret, seams);
+
+    let issues = validate_collisions_with(&ret, prototypes)?;
+    dedup_tiles(&mut ret);
+    Ok((ret, issues))
+}
+
+// The two poles closest together when the second is moved over by shift, as long as they can
+// reach each other - a pole's reach is only as far as the pole at the other end can manage too
+fn nearest_pair<F: Fn(&Entity) -> bool>(
+    poles: &[(&Entity, f64)],
+    shift: (f64, f64),
+    usable: F,
+) -> Option<(i32, i32)> {
+    let mut best: Option<(f64, i32, i32)> = None;
+    for &(a, reach_a) in poles.iter().filter(|(e, _)| usable(e)) {
+        for &(b, reach_b) in poles.iter().filter(|(e, _)| usable(e)) {
+            let ((ax, ay), (bx, by)) = (a.position.to_f64(), b.position.to_f64());
+            let d = (bx + shift.0 - ax).hypot(by + shift.1 - ay);
+            if d <= reach_a.min(reach_b) && best.map_or(true, |(best_d, _, _)| d < best_d) {
+                best = Some((d, a.entity_number, b.entity_number));
+            }
+        }
+    }
+    best.map(|(_, a, b)| (a, b))
+}
+
+fn last_number(bp: &Blueprint) -> i32 {
+    bp.entities
+        .iter()
+        .map(|e| e.entity_number)
+        .max()
+        .unwrap_or(0)
+}
+
+// b moved over by offset and numbered on from first, added to the end of everything in bp
+fn append(bp: &mut Blueprint, b: &Blueprint, offset: Position, first: i32) {
+    let mut b = b.clone();
+    let numbers = b
+        .entities
+        .iter()
+        .map(|e| (e.entity_number, e.entity_number + first))
+        .collect();
+    renumber(&mut b, &numbers);
+    for e in &mut b.entities {
+        e.position = e.position + offset;
+    }
+    for t in b.tiles.iter_mut().flatten() {
+        t.position = t.position + offset;
+    }
+    bp.entities.extend(b.entities);
+    bp.wires.extend(b.wires);
+    bp.schedules.extend(b.schedules);
+    bp.stock_connections.extend(b.stock_connections);
+    if let Some(tiles) = b.tiles {
+        bp.tiles.get_or_insert_with(Vec::new).extend(tiles);
+    }
+}
+
+// once doubled up tiles have been reported, only the last of each is kept
+fn dedup_tiles(bp: &mut Blueprint) {
+    if let Some(ref mut tiles) = bp.tiles {
+        let last: HashMap<Position, usize> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.position, i))
+            .collect();
+        let mut i = 0;
+        tiles.retain(|t| {
+            i += 1;
+            last[&t.position] == i - 1
+        });
+    }
+}
+
+// Pole to pole wires, written whichever way the blueprint's schema wants them
+fn add_wires(bp: &mut Blueprint, wires: Vec<Wire>) {
+    if bp.schema() == Schema::V2 {
+        bp.wires.extend(wires);
+        return;
+    }
+    // legacy ones go on both ends - copper as neighbours, circuits on connection point 1
+    let index: HashMap<i32, usize> = bp
+        .entities
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.entity_number, i))
+        .collect();
+    for w in wires {
+        for &(from, to) in &[(w.from, w.to), (w.to, w.from)] {
+            let e = &mut bp.entities[index[&from.entity]];
+            let red = match from.connector {
+                WireConnector::PoleCopper => {
+                    e.neighbours.get_or_insert_with(Vec::new).push(to.entity);
+                    continue;
+                }
+                WireConnector::CircuitRed => true,
+                _ => false,
+            };
+            let conn = e.connections.get_or_insert_with(|| Connection {
+                one: None,
+                two: None,
+                cu0: None,
+                cu1: None,
+                extra: Extra::new(),
+            });
+            let point = conn.one.get_or_insert_with(|| ConnectionPoint {
+                red: Vec::new(),
+                green: Vec::new(),
+                extra: Extra::new(),
+            });
+            let targets = if red {
+                &mut point.red
+            } else {
+                &mut point.green
+            };
+            targets.push(ConnectionData {
+                entity_id: to.entity,
+                circuit_id: None,
+                extra: Extra::new(),
+            });
+        }
+    }
+}
+
+// A new blueprint with just the entities keep picks out, e.g. all the combinators - no tiles
+pub fn extract<F: Fn(&Entity) -> bool>(bp: &Blueprint, keep: F) -> Result<Blueprint> {
+    extract_with(bp, keep, Prototypes::vanilla())
+}
+
+pub fn extract_with<F: Fn(&Entity) -> bool>(
+    bp: &Blueprint,
+    keep: F,
+    prototypes: &Prototypes,
+) -> Result<Blueprint> {
+    let numbers = bp
+        .entities
+        .iter()
+        .filter(|e| keep(e))
+        .map(|e| e.entity_number)
+        .collect();
+    cut(bp, &numbers, None, prototypes)
+}
+
+// A new blueprint with whatever reaches into region, the way the game's selection box picks
+// things up - tiles too
+pub fn extract_region(bp: &Blueprint, region: &TileRect) -> Result<Blueprint> {
+    extract_region_with(bp, region, Prototypes::vanilla())
+}
+
+pub fn extract_region_with(
+    bp: &Blueprint,
+    region: &TileRect,
+    prototypes: &Prototypes,
+) -> Result<Blueprint> {
+    let system = bp.direction_system();
+    let mut numbers = HashSet::new();
+    for e in &bp.entities {
+        if !prototypes
+            .footprint(e, system)?
+            .intersection(region)
+            .is_empty()
+        {
+            numbers.insert(e.entity_number);
+        }
+    }
+    cut(bp, &numbers, Some(region), prototypes)
+}
+
+// Everything in numbers, numbered from 1 in the order they were in, with wires, schedules and
+// couplings to anything else dropped. It's moved back over 0,0 in steps of two tiles so rails stay
+// on their grid
+fn cut(
+    bp: &Blueprint,
+    numbers: &HashSet<i32>,
+    region: Option<&TileRect>,
+    prototypes: &Prototypes,
+) -> Result<Blueprint> {
+    let mut ret = bp.clone();
+    let renumbered = ret
+        .entities
+        .iter()
+        .filter(|e| numbers.contains(&e.entity_number))
+        .enumerate()
+        .map(|(i, e)| (e.entity_number, i as i32 + 1))
+        .collect();
+    renumber(&mut ret, &renumbered);
+    ret.tiles = match region {
+        Some(region) => ret.tiles.map(|tiles| {
+            tiles
+                .into_iter()
+                .filter(|t| {
+                    let (x, y) = t.position.tile();
+                    region.contains(x, y)
+                })
+                .collect()
+        }),
+        None => None,
+    };
+    if ret.tiles.as_ref().is_some_and(Vec::is_empty) {
+        ret.tiles = None;
+    }
+
+    let bounds = ret.bounding_box_with(prototypes)?;
+    if !bounds.is_empty() {
+        let offset = Position {
+            x: -Fixed::from_tiles((bounds.left + bounds.right).div_euclid(4) * 2),
+            y: -Fixed::from_tiles((bounds.top + bounds.bottom).div_euclid(4) * 2),
+        };
+        for e in &mut ret.entities {
+            e.position = e.position + offset;
+        }
+        for t in ret.tiles.iter_mut().flatten() {
+            t.position = t.position + offset;
+        }
+    }
+    Ok(ret)
+}
+
+// Gives every entity in numbers its new entity_number and drops the rest, along with any wire
+// to them and any schedule left without a locomotive
+fn renumber(bp: &mut Blueprint, numbers: &HashMap<i32, i32>) {
+    bp.entities
+        .retain(|e| numbers.contains_key(&e.entity_number));
+    for e in &mut bp.entities {
+        e.entity_number = numbers[&e.entity_number];
+
+        // legacy wires live on the entities at either end
+        if let Some(mut conn) = e.connections.take() {
+            for point in [&mut conn.one, &mut conn.two] {
+                if let Some(mut p) = point.take() {
+                    remap(&mut p.red, numbers, |w| &mut w.entity_id);
+                    remap(&mut p.green, numbers, |w| &mut w.entity_id);
+                    if !(p.red.is_empty() && p.green.is_empty() && p.extra.is_empty()) {
+                        *point = Some(p);
+                    }
+                }
+            }
+            for side in [&mut conn.cu0, &mut conn.cu1] {
+                if let Some(mut targets) = side.take() {
+                    remap(&mut targets, numbers, |w| &mut w.entity_id);
+                    if !targets.is_empty() {
+                        *side = Some(targets);
+                    }
+                }
+            }
+            let empty = conn.one.is_none()
+                && conn.two.is_none()
+                && conn.cu0.is_none()
+                && conn.cu1.is_none()
+                && conn.extra.is_empty();
+            if !empty {
+                e.connections = Some(conn);
+            }
+        }
+        if let Some(mut neighbours) = e.neighbours.take() {
+            remap(&mut neighbours, numbers, |n| n);
+            if !neighbours.is_empty() {
+                e.neighbours = Some(neighbours);
+            }
+        }
+    }
+
+    remap(&mut bp.wires, numbers, |w| &mut w.from.entity);
+    remap(&mut bp.wires, numbers, |w| &mut w.to.entity);
+    for s in &mut bp.schedules {
+        remap(&mut s.locomotives, numbers, |n| n);
+    }
+    bp.schedules.retain(|s| !s.locomotives.is_empty());
+    // a wagon whose neighbour didn't come along just isn't coupled on that end any more
+    remap(&mut bp.stock_connections, numbers, |c| &mut c.stock);
+    for c in &mut bp.stock_connections {
+        for end in [&mut c.front, &mut c.back] {
+            *end = end.and_then(|n| numbers.get(&n).cloned());
+        }
+    }
+    bp.stock_connections
+        .retain(|c| c.front.is_some() || c.back.is_some());
+}
+
+// renumbers what id points at in each of items, dropping the ones with no new number
+fn remap<T, F>(items: &mut Vec<T>, numbers: &HashMap<i32, i32>, id: F)
+where
+    F: Fn(&mut T) -> &mut i32,
+{
+    items.retain_mut(|t| match numbers.get(id(t)) {
+        Some(&n) => {
+            *id(t) = n;
+            true
+        }
+        None => false,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::blueprint;
+
+    // a pole wired to a combinator and to another pole, and a locomotive with a schedule
+    const MODULE: &str = r#""entities":[
+        {"entity_number":1,"name":"medium-electric-pole","position":{"x":0.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":2,"circuit_id":1}]}},"neighbours":[3]},
+        {"entity_number":2,"name":"arithmetic-combinator","position":{"x":2,"y":0.5},"direction":2,"connections":{"1":{"red":[{"entity_id":1}]}}},
+        {"entity_number":3,"name":"medium-electric-pole","position":{"x":5.5,"y":0.5},"neighbours":[1]},
+        {"entity_number":4,"name":"locomotive","position":{"x":3,"y":6},"orientation":0.25}
+    ],"tiles":[{"name":"concrete","position":{"x":0,"y":0}}],
+    "schedules":[{"locomotives":[4],"schedule":[{"station":"Home"}]}]"#;
+
+    #[test]
+    fn test_merge_renumbers() {
+        let a = blueprint(281479275675648, MODULE);
+        let (merged, issues) = merge(&a, &a, Position::new(10.0, 0.0)).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(merged.entities.len(), 8);
+        assert_eq!(&merged.entities[..4], &a.entities[..]);
+
+        let b = &merged.entities[4..];
+        assert_eq!(
+            b.iter().map(|e| e.entity_number).collect::<Vec<_>>(),
+            vec![5, 6, 7, 8]
+        );
+        assert_eq!(b[0].position, Position::new(10.5, 0.5));
+        assert_eq!(b[0].neighbours, Some(vec![7]));
+        assert_eq!(b[2].neighbours, Some(vec![5]));
+        assert_eq!(
+            b[0].legacy_wires(),
+            vec![
+                Wire::new(
+                    WireEnd::new(5, WireConnector::CircuitRed),
+                    WireEnd::new(6, WireConnector::CircuitRed)
+                ),
+                Wire::new(
+                    WireEnd::new(5, WireConnector::PoleCopper),
+                    WireEnd::new(7, WireConnector::PoleCopper)
+                )
+            ]
+        );
+        assert_eq!(
+            b[1].connections.as_ref().unwrap().one.as_ref().unwrap().red[0].entity_id,
+            5
+        );
+        assert_eq!(merged.schedules.len(), 2);
+        assert_eq!(merged.schedules[1].locomotives, vec![8]);
+        let tiles = merged.tiles.as_ref().unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[1].position, Position::new(10.0, 0.0));
+    }
+
+    #[test]
+    fn test_stock_connections_follow_renumbering() {
+        use types::StockConnection;
+
+        let coupled = |stock, front, back| StockConnection {
+            stock,
+            front,
+            back,
+            extra: Extra::new(),
+        };
+        let train = blueprint(
+            562949954076672,
+            r#""entities":[
+                {"entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5}},
+                {"entity_number":2,"name":"locomotive","position":{"x":3,"y":3},"orientation":0.25},
+                {"entity_number":3,"name":"cargo-wagon","position":{"x":10,"y":3},"orientation":0.25}
+            ],"stock_connections":[{"stock":2,"back":3},{"stock":3,"front":2}]"#,
+        );
+
+        let (merged, _) = merge(&train, &train, Position::new(0.0, 4.0)).unwrap();
+        assert_eq!(
+            merged.stock_connections[2..].to_vec(),
+            vec![coupled(5, None, Some(6)), coupled(6, Some(5), None)]
+        );
+
+        let (row, _) = tile(&train, 2, 1, 0).unwrap();
+        assert_eq!(row.stock_connections.len(), 4);
+        assert_eq!(row.stock_connections[3], coupled(6, Some(5), None));
+
+        // the locomotive on its own isn't coupled to anything
+        let loco = extract(&train, |e| e.name == "locomotive").unwrap();
+        assert_eq!(loco.stock_connections, vec![]);
+        let stock = extract(&train, |e| e.name != "small-lamp").unwrap();
+        assert_eq!(
+            stock.stock_connections,
+            vec![coupled(1, None, Some(2)), coupled(2, Some(1), None)]
+        );
+    }
+
+    #[test]
+    fn test_merge_2_0_wires() {
+        let a = blueprint(
+            562949954076672,
+            r#""entities":[
+                {"entity_number":1,"name":"small-electric-pole","position":{"x":0.5,"y":0.5}},
+                {"entity_number":2,"name":"small-electric-pole","position":{"x":4.5,"y":0.5}}
+            ],"wires":[[1,5,2,5],[1,1,2,1]]"#,
+        );
+        let (merged, issues) = merge(&a, &a, Position::new(0.0, 3.0)).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(
+            merged.wires[2..].to_vec(),
+            vec![
+                Wire::new(
+                    WireEnd::new(3, WireConnector::PoleCopper),
+                    WireEnd::new(4, WireConnector::PoleCopper)
+                ),
+                Wire::new(
+                    WireEnd::new(3, WireConnector::CircuitRed),
+                    WireEnd::new(4, WireConnector::CircuitRed)
+                ),
+            ]
+        );
+
+        let legacy = blueprint(281479275675648, MODULE);
+        match merge(&legacy, &a, Position::default()) {
+            Err(Error::SchemaMismatch(..)) => (),
+            other => panic!("expected a schema mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_merge_reports_collisions() {
+        let a = blueprint(281479275675648, MODULE);
+        // stamped right on top of itself, everything lands on its twin
+        let (merged, issues) = merge(&a, &a, Position::default()).unwrap();
+        let overlap = |a: &Entity, b: &Entity| CollisionIssue::Overlap {
+            a: a.entity_number,
+            a_position: a.position,
+            b: b.entity_number,
+            b_position: b.position,
+        };
+        let e = &merged.entities;
+        assert_eq!(
+            issues,
+            vec![
+                overlap(&e[0], &e[4]),
+                overlap(&e[1], &e[5]),
+                overlap(&e[2], &e[6]),
+                overlap(&e[3], &e[7]),
+                CollisionIssue::TileOverlap {
+                    position: Position::new(0.0, 0.0)
+                },
+            ]
+        );
+        assert_eq!(merged.tiles.as_ref().unwrap().len(), 1);
+
+        // half a tile over puts b off the grid a is on
+        let (_, issues) = merge(&a, &a, Position::new(0.5, 20.0)).unwrap();
+        assert!(issues.contains(&CollisionIssue::OffGrid {
+            entity: 5,
+            position: Position::new(1.0, 20.5),
+        }));
+    }
+
+    #[test]
+    fn test_extract_combinators() {
+        let bp = blueprint(
+            562949954076672,
+            r#""entities":[
+                {"entity_number":1,"name":"small-electric-pole","position":{"x":20.5,"y":10.5}},
+                {"entity_number":2,"name":"decider-combinator","position":{"x":22,"y":10.5},"direction":4},
+                {"entity_number":3,"name":"constant-combinator","position":{"x":23.5,"y":10.5}},
+                {"entity_number":4,"name":"small-lamp","position":{"x":24.5,"y":10.5}}
+            ],"wires":[[1,1,2,1],[2,3,3,1],[3,1,4,1]]"#,
+        );
+        let cut = extract(&bp, |e| e.name.ends_with("-combinator")).unwrap();
+        assert_eq!(
+            cut.entities
+                .iter()
+                .map(|e| (e.entity_number, e.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "decider-combinator"), (2, "constant-combinator")]
+        );
+        // only the wire between the two combinators is left
+        assert_eq!(
+            cut.wires,
+            vec![Wire::new(
+                WireEnd::new(1, WireConnector::CombinatorOutputRed),
+                WireEnd::new(2, WireConnector::CircuitRed)
+            )]
+        );
+        // x 21..24 and y 10..11, moved by (-22, -10)
+        assert_eq!(cut.entities[0].position, Position::new(0.0, 0.5));
+        assert_eq!(cut.entities[1].position, Position::new(1.5, 0.5));
+        assert!(cut.tiles.is_none());
+        // the original is untouched
+        assert_eq!(bp.entities.len(), 4);
+    }
+
+    #[test]
+    fn test_extract_region() {
+        let bp = blueprint(281479275675648, MODULE);
+        let cut = extract_region(&bp, &TileRect::new(0, 0, 2, 2)).unwrap();
+        // the combinator pokes into the region, the far pole and the train don't
+        assert_eq!(cut.entities.len(), 2);
+        assert_eq!(cut.entities[0].neighbours, None);
+        assert_eq!(cut.entities[0].legacy_wires().len(), 1);
+        assert_eq!(cut.entities[1].legacy_wires().len(), 1);
+        assert!(cut.schedules.is_empty());
+        assert_eq!(cut.tiles.as_ref().unwrap().len(), 1);
+        // x 0..3, which is close enough to the middle already
+        assert_eq!(cut.entities[0].position, Position::new(0.5, 0.5));
+
+        let empty = extract_region(&bp, &TileRect::new(-10, -10, -5, -5)).unwrap();
+        assert!(empty.entities.is_empty());
+        assert!(empty.tiles.is_none());
+    }
+
+    #[test]
+    fn test_tile_smelters() {
+        let bp = blueprint(
+            281479275675648,
+            r#""entities":[
+                {"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":0.5},"direction":4},
+                {"entity_number":2,"name":"transport-belt","position":{"x":0.5,"y":1.5},"direction":4},
+                {"entity_number":3,"name":"inserter","position":{"x":1.5,"y":0.5},"direction":6},
+                {"entity_number":4,"name":"stone-furnace","position":{"x":3,"y":1}}
+            ],"tiles":[{"name":"stone-path","position":{"x":0,"y":0}}]"#,
+        );
+        let (column, issues) = tile(&bp, 1, 5, 0).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(column.entities.len(), 20);
+        assert_eq!(
+            column
+                .entities
+                .iter()
+                .map(|e| e.entity_number)
+                .collect::<Vec<_>>(),
+            (1..=20).collect::<Vec<_>>()
+        );
+        assert_eq!(column.entities[19].position, Position::new(3.0, 9.0));
+        assert_eq!(column.tiles.as_ref().unwrap().len(), 5);
+        let size = column.bounding_box().unwrap();
+        assert_eq!((size.width(), size.height()), (4, 10));
+
+        // pulled in by a tile, the furnace lands on both of the next copy's belts
+        let (_, issues) = tile(&bp, 2, 1, -1).unwrap();
+        assert_eq!(issues.len(), 2);
+    }
+
+    // a pole with a lamp hanging off it on red
+    const LAMP: &str = r#""entities":[
+        {"entity_number":1,"name":"small-electric-pole","position":{"x":0.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":2}]}}},
+        {"entity_number":2,"name":"small-lamp","position":{"x":1.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":1}]}}}
+    ]"#;
+
+    #[test]
+    fn test_tile_wires_seams() {
+        let bp = blueprint(281479275675648, LAMP);
+        let wiring = SeamWiring {
+            copper: true,
+            circuits: true,
+        };
+        let (field, issues) = tile_wired(&bp, 3, 2, 1, wiring).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(field.entities.len(), 12);
+        assert_eq!(field.entities[4].position, Position::new(6.5, 0.5));
+        assert_eq!(field.entities[6].position, Position::new(0.5, 2.5));
+
+        // each pole joins the ones right of and below it, on copper and red but not green
+        assert_eq!(field.entities[0].neighbours, Some(vec![3, 7]));
+        assert_eq!(field.entities[2].neighbours, Some(vec![1, 5, 9]));
+        let point = field.entities[0]
+            .connections
+            .as_ref()
+            .unwrap()
+            .one
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            point.red.iter().map(|c| c.entity_id).collect::<Vec<_>>(),
+            vec![2, 3, 7]
+        );
+        assert!(point.green.is_empty());
+        // 6 lamps, 7 seams on copper and 7 on red
+        assert_eq!(field.normalized_wires().len(), 20);
+
+        // too far apart for a small pole to reach
+        let (field, _) = tile_wired(&bp, 3, 2, 10, wiring).unwrap();
+        assert_eq!(field.entities[0].neighbours, None);
+        assert_eq!(field.normalized_wires().len(), 6);
+    }
+
+    #[test]
+    fn test_tile_wires_poles_either_side_of_a_seam() {
+        // 10 wide, more than a small pole reaches, but the right pole is next to the next copy's left
+        let bp = blueprint(
+            281479275675648,
+            r#""entities":[
+                {"entity_number":1,"name":"small-electric-pole","position":{"x":0.5,"y":0.5}},
+                {"entity_number":2,"name":"small-electric-pole","position":{"x":9.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":3}]}}},
+                {"entity_number":3,"name":"small-lamp","position":{"x":8.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":2}]}}}
+            ]"#,
+        );
+        let wiring = SeamWiring {
+            copper: true,
+            circuits: true,
+        };
+        let (row, issues) = tile_wired(&bp, 3, 1, 0, wiring).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(row.entities[1].neighbours, Some(vec![4]));
+        assert_eq!(row.entities[3].neighbours, Some(vec![2]));
+        assert_eq!(row.entities[4].neighbours, Some(vec![7]));
+        // only the right pole has red on it, and it's 10 from its twin, so no red goes across
+        assert_eq!(
+            row.normalized_wires()
+                .iter()
+                .filter(|w| w.from.connector == WireConnector::PoleCopper)
+                .count(),
+            2
+        );
+        assert_eq!(row.normalized_wires().len(), 5);
+    }
+
+    #[test]
+    fn test_tile_keeps_rails_on_their_grid() {
+        // 3 wide with the signal's column, so copies have to be 4 apart
+        let bp = blueprint(
+            281479275675648,
+            r#""entities":[
+                {"entity_number":1,"name":"straight-rail","position":{"x":1,"y":1}},
+                {"entity_number":2,"name":"rail-signal","position":{"x":2.5,"y":0.5}}
+            ]"#,
+        );
+        let (row, issues) = tile(&bp, 3, 1, 0).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(row.entities[2].position, Position::new(5.0, 1.0));
+        assert_eq!(row.entities[4].position, Position::new(9.0, 1.0));
+
+        // and an odd spacing gets rounded up the same way
+        let (column, issues) = tile(&bp, 1, 2, 1).unwrap();
+        assert_eq!(issues, vec![]);
+        assert_eq!(column.entities[2].position, Position::new(1.0, 5.0));
+    }
+
+    #[test]
+    fn test_tile_2_0_wires() {
+        let bp = blueprint(
+            562949954076672,
+            r#""entities":[
+                {"entity_number":1,"name":"small-electric-pole","position":{"x":0.5,"y":0.5}},
+                {"entity_number":2,"name":"small-lamp","position":{"x":1.5,"y":0.5}}
+            ],"wires":[[1,1,2,1]]"#,
+        );
+        let wiring = SeamWiring {
+            copper: true,
+            circuits: false,
+        };
+        let (row, _) = tile_wired(&bp, 2, 1, 0, wiring).unwrap();
+        assert_eq!(
+            row.wires,
+            vec![
+                Wire::new(
+                    WireEnd::new(1, WireConnector::CircuitRed),
+                    WireEnd::new(2, WireConnector::CircuitRed)
+                ),
+                Wire::new(
+                    WireEnd::new(3, WireConnector::CircuitRed),
+                    WireEnd::new(4, WireConnector::CircuitRed)
+                ),
+                Wire::new(
+                    WireEnd::new(1, WireConnector::PoleCopper),
+                    WireEnd::new(3, WireConnector::PoleCopper)
+                ),
+            ]
+        );
+    }
+}
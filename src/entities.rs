@@ -1,31 +1,780 @@
 // this is where I define the actual shapes and sizes
 
+use error::{Error, Result};
 use geo::{LineString, Point, Polygon};
-use std::str::FromStr;
-use types::Entity;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use std::{collections::HashMap, f64::consts::PI, sync::OnceLock};
+use types::{Direction16, DirectionSystem, Entity, TileRect};
 
-// this maps the keys coming in from the blueprint string to Polygons
-//pub static ENTITIES: HashMap<String, Polygon>;
+// Rough grouping, mostly so callers can pick out belts or rails without a name list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntityCategory {
+    Belt,
+    Inserter,
+    Storage,
+    Fluid,
+    Power,
+    Production,
+    Circuit,
+    Rail,
+    RollingStock,
+    Defense,
+    Logistics,
+    Other,
+}
 
-pub enum EntityType {
-    TransportBelt,
-    Splitter,
-    UndergroundBelt,
+// How the footprint responds to the entity's direction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RotationBehaviour {
+    Fixed,       // direction is ignored (chests, poles, furnaces...)
+    Cardinal,    // four ways, width and height swap facing east or west
+    AllHeadings, // every heading the direction system has - diagonals included
+    CurvedRail,  // 1.1 curves - 8-way direction d is d/2 quarter turns, and odd ones are mirrored
+    Orientation, // rolling stock turns freely with `orientation`
 }
 
-impl FromStr for EntityType {
-    type Err = String; // for now
+// Collision box relative to the entity's position, facing north
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollisionBox {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "transport-belt" => Ok(EntityType::TransportBelt),
-            "splitter" => Ok(EntityType::Splitter),
-            "underground-belt" => Ok(EntityType::UndergroundBelt),
-            _ => Err("I don't know that entity!".into()),
+impl CollisionBox {
+    pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        CollisionBox { x1, y1, x2, y2 }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.x2 - self.x1
+    }
+
+    pub fn height(&self) -> f64 {
+        self.y2 - self.y1
+    }
+
+    // corners clockwise from the top left
+    fn corners(&self) -> [(f64, f64); 4] {
+        [
+            (self.x1, self.y1),
+            (self.x2, self.y1),
+            (self.x2, self.y2),
+            (self.x1, self.y2),
+        ]
+    }
+
+    // the box after turning it `quarters` times clockwise
+    pub fn rotated(&self, quarters: u8) -> Self {
+        let (a, b) = (
+            rotate_quarters((self.x1, self.y1), quarters),
+            rotate_quarters((self.x2, self.y2), quarters),
+        );
+        CollisionBox::new(a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prototype {
+    pub name: String,
+    pub category: EntityCategory,
+    pub collision_box: CollisionBox,
+    pub tile_width: u32, // facing north
+    pub tile_height: u32,
+    pub rotation: RotationBehaviour,
+    pub selection_box: Option<CollisionBox>,
+    pub fast_replaceable_group: Option<String>, // entities in the same group can be upgraded in place
+    pub crafting_categories: Vec<String>,       // assemblers, furnaces and the like
+    pub belt_speed: Option<f64>,                // tiles per tick, belts only
+    pub wire_reach: Option<f64>,                // tiles, electric poles only
+}
+
+impl Prototype {
+    // how far round the entity is turned, in whole turns clockwise from north
+    pub fn turns(&self, e: &Entity, system: DirectionSystem) -> f64 {
+        match self.rotation {
+            RotationBehaviour::Fixed => 0.0,
+            RotationBehaviour::Orientation => e.orientation.unwrap_or(0.0),
+            RotationBehaviour::Cardinal | RotationBehaviour::AllHeadings => {
+                f64::from(heading(e, system) as u8) / 16.0
+            }
+            // each pair of directions shares a quarter turn
+            RotationBehaviour::CurvedRail => f64::from(heading(e, system) as u8 / 4) / 4.0,
         }
     }
+
+    // whether the shape is flipped left to right before it's turned
+    pub fn mirrored(&self, e: &Entity, system: DirectionSystem) -> bool {
+        self.rotation == RotationBehaviour::CurvedRail && (heading(e, system) as u8 / 2) % 2 == 1
+    }
+
+    // tile footprint at a heading - anything in between rounds to the nearest quarter turn
+    pub fn tile_size(&self, turns: f64) -> (u32, u32) {
+        if nearest_quarter(turns) % 2 == 1 {
+            (self.tile_height, self.tile_width)
+        } else {
+            (self.tile_width, self.tile_height)
+        }
+    }
+
+    // The tiles an entity of this type covers where it's placed
+    // Rolling stock isn't on the tile grid, so it gets every tile its turned collision box touches
+    pub fn footprint(&self, e: &Entity, system: DirectionSystem) -> TileRect {
+        let (x, y) = e.position.to_f64();
+        let turns = self.turns(e, system);
+        if self.rotation == RotationBehaviour::Orientation {
+            let corners: Vec<(f64, f64)> = self
+                .collision_box
+                .corners()
+                .iter()
+                .map(|&c| rotate(c, turns))
+                .collect();
+            let min = |f: fn(&(f64, f64)) -> f64| corners.iter().map(f).fold(f64::MAX, f64::min);
+            let max = |f: fn(&(f64, f64)) -> f64| corners.iter().map(f).fold(f64::MIN, f64::max);
+            return TileRect::covering(
+                x + min(|c| c.0),
+                y + min(|c| c.1),
+                x + max(|c| c.0),
+                y + max(|c| c.1),
+            );
+        }
+        let (w, h) = self.tile_size(turns);
+        let (half_w, half_h) = (f64::from(w) / 2.0, f64::from(h) / 2.0);
+        TileRect::covering(x - half_w, y - half_h, x + half_w, y + half_h)
+    }
 }
 
+impl Prototype {
+    fn from_data_raw(prototype_type: &str, name: &str, proto: &Value, path: &str) -> Result<Self> {
+        let collision_box: CollisionBox = raw_field(proto, "collision_box", path)?
+            .map(|[[x1, y1], [x2, y2]]: [[f64; 2]; 2]| CollisionBox::new(x1, y1, x2, y2))
+            .unwrap_or_else(|| CollisionBox::new(0.0, 0.0, 0.0, 0.0));
+        let selection_box = raw_field(proto, "selection_box", path)?
+            .map(|[[x1, y1], [x2, y2]]: [[f64; 2]; 2]| CollisionBox::new(x1, y1, x2, y2));
+        // the game rounds the box out to whole tiles unless it's told otherwise
+        let tile_width = raw_field(proto, "tile_width", path)?
+            .unwrap_or_else(|| collision_box.width().ceil().max(1.0) as u32);
+        let tile_height = raw_field(proto, "tile_height", path)?
+            .unwrap_or_else(|| collision_box.height().ceil().max(1.0) as u32);
+        let category = category_for_type(prototype_type);
+        // an empty list comes out of the game's JSON writer as {}, so flags are read loosely
+        let not_rotatable = proto
+            .get("flags")
+            .and_then(Value::as_array)
+            .is_some_and(|flags| flags.iter().any(|f| f == "not-rotatable"));
+        let rotation = if not_rotatable {
+            RotationBehaviour::Fixed
+        } else {
+            rotation_for_type(prototype_type)
+        };
+        let belt_speed = if category == EntityCategory::Belt {
+            raw_field(proto, "speed", path)?
+        } else {
+            None
+        };
+        let wire_reach = if prototype_type == "electric-pole" {
+            raw_field(proto, "maximum_wire_distance", path)?
+        } else {
+            None
+        };
+        Ok(Prototype {
+            name: name.into(),
+            category,
+            collision_box,
+            tile_width,
+            tile_height,
+            rotation,
+            selection_box,
+            fast_replaceable_group: raw_field(proto, "fast_replaceable_group", path)?,
+            crafting_categories: match proto.get("crafting_categories") {
+                Some(Value::Array(_)) => {
+                    raw_field(proto, "crafting_categories", path)?.unwrap_or_default()
+                }
+                _ => Vec::new(),
+            },
+            belt_speed,
+            wire_reach,
+        })
+    }
+}
+
+fn raw_field<T: DeserializeOwned>(proto: &Value, key: &str, path: &str) -> Result<Option<T>> {
+    match proto.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => serde_json::from_value(v.clone())
+            .map(Some)
+            .map_err(|source| Error::Prototype {
+                path: format!("{}.{}", path, key),
+                source,
+            }),
+    }
+}
+
+fn category_for_type(prototype_type: &str) -> EntityCategory {
+    match prototype_type {
+        "transport-belt" | "underground-belt" | "splitter" | "loader" | "loader-1x1"
+        | "linked-belt" | "lane-splitter" => EntityCategory::Belt,
+        "inserter" => EntityCategory::Inserter,
+        "container" | "logistic-container" | "infinity-container" | "linked-container"
+        | "cargo-landing-pad" => EntityCategory::Storage,
+        "pipe" | "pipe-to-ground" | "pump" | "storage-tank" | "offshore-pump" | "infinity-pipe"
+        | "valve" => EntityCategory::Fluid,
+        "electric-pole"
+        | "boiler"
+        | "generator"
+        | "burner-generator"
+        | "solar-panel"
+        | "accumulator"
+        | "reactor"
+        | "heat-pipe"
+        | "heat-interface"
+        | "electric-energy-interface"
+        | "power-switch"
+        | "fusion-generator"
+        | "fusion-reactor"
+        | "lightning-attractor" => EntityCategory::Power,
+        "mining-drill" | "furnace" | "assembling-machine" | "lab" | "beacon" | "rocket-silo"
+        | "agricultural-tower" | "asteroid-collector" => EntityCategory::Production,
+        "arithmetic-combinator"
+        | "decider-combinator"
+        | "selector-combinator"
+        | "constant-combinator"
+        | "programmable-speaker"
+        | "lamp"
+        | "display-panel" => EntityCategory::Circuit,
+        "straight-rail"
+        | "curved-rail"
+        | "half-diagonal-rail"
+        | "curved-rail-a"
+        | "curved-rail-b"
+        | "legacy-straight-rail"
+        | "legacy-curved-rail"
+        | "rail-ramp"
+        | "elevated-straight-rail"
+        | "elevated-half-diagonal-rail"
+        | "elevated-curved-rail-a"
+        | "elevated-curved-rail-b"
+        | "rail-support"
+        | "rail-signal"
+        | "rail-chain-signal"
+        | "train-stop" => EntityCategory::Rail,
+        "locomotive" | "cargo-wagon" | "fluid-wagon" | "artillery-wagon" => {
+            EntityCategory::RollingStock
+        }
+        "wall" | "gate" | "land-mine" | "ammo-turret" | "electric-turret" | "fluid-turret"
+        | "artillery-turret" | "radar" => EntityCategory::Defense,
+        "roboport" => EntityCategory::Logistics,
+        _ => EntityCategory::Other,
+    }
+}
+
+fn rotation_for_type(prototype_type: &str) -> RotationBehaviour {
+    match category_for_type(prototype_type) {
+        EntityCategory::Belt | EntityCategory::Inserter => RotationBehaviour::Cardinal,
+        EntityCategory::RollingStock => RotationBehaviour::Orientation,
+        EntityCategory::Rail if prototype_type == "train-stop" => RotationBehaviour::Cardinal,
+        EntityCategory::Rail
+            if prototype_type == "curved-rail" || prototype_type == "legacy-curved-rail" =>
+        {
+            RotationBehaviour::CurvedRail
+        }
+        EntityCategory::Rail => RotationBehaviour::AllHeadings,
+        _ => match prototype_type {
+            "pipe-to-ground"
+            | "pump"
+            | "storage-tank"
+            | "offshore-pump"
+            | "boiler"
+            | "generator"
+            | "mining-drill"
+            | "assembling-machine"
+            | "arithmetic-combinator"
+            | "decider-combinator"
+            | "selector-combinator"
+            | "constant-combinator"
+            | "display-panel"
+            | "gate"
+            | "fluid-turret"
+            | "valve"
+            | "fusion-reactor"
+            | "fusion-generator"
+            | "asteroid-collector" => RotationBehaviour::Cardinal,
+            _ => RotationBehaviour::Fixed,
+        },
+    }
+}
+
+fn heading(e: &Entity, system: DirectionSystem) -> Direction16 {
+    e.direction
+        .and_then(|d| d.in_system(system))
+        .unwrap_or(Direction16::North)
+}
+
+fn nearest_quarter(turns: f64) -> u8 {
+    ((turns * 4.0).round() as i64).rem_euclid(4) as u8
+}
+
+// exact for right angles, so a rotated 1x2 stays exactly 2x1
+fn rotate_quarters((x, y): (f64, f64), quarters: u8) -> (f64, f64) {
+    match quarters % 4 {
+        0 => (x, y),
+        1 => (-y, x),
+        2 => (-x, -y),
+        _ => (y, -x),
+    }
+}
+
+// clockwise on screen, where y points down
+fn rotate((x, y): (f64, f64), turns: f64) -> (f64, f64) {
+    let quarters = turns * 4.0;
+    if quarters == quarters.round() {
+        return rotate_quarters((x, y), quarters.rem_euclid(4.0) as u8);
+    }
+    let (sin, cos) = (turns * 2.0 * PI).sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+// Every prototype we know about by name
+#[derive(Clone, Debug, Default)]
+pub struct Prototypes {
+    by_name: HashMap<String, Prototype>,
+}
+
+impl Prototypes {
+    // the base game, 1.1 and 2.0 names both
+    pub fn vanilla() -> &'static Prototypes {
+        static VANILLA: OnceLock<Prototypes> = OnceLock::new();
+        VANILLA.get_or_init(|| {
+            let mut p = Prototypes::default();
+            for &(name, category, (x1, y1, x2, y2), (w, h), rotation) in VANILLA_PROTOTYPES {
+                p.insert(Prototype {
+                    name: name.into(),
+                    category,
+                    collision_box: CollisionBox::new(x1, y1, x2, y2),
+                    tile_width: w,
+                    tile_height: h,
+                    rotation,
+                    selection_box: None,
+                    fast_replaceable_group: None,
+                    crafting_categories: Vec::new(),
+                    belt_speed: None,
+                    wire_reach: None,
+                });
+            }
+            for &(group, names) in VANILLA_REPLACEABLE_GROUPS {
+                for name in names {
+                    p.by_name.get_mut(*name).unwrap().fast_replaceable_group = Some(group.into());
+                }
+            }
+            for &(speed, names) in VANILLA_BELT_SPEEDS {
+                for name in names {
+                    p.by_name.get_mut(*name).unwrap().belt_speed = Some(speed);
+                }
+            }
+            for &(name, reach) in VANILLA_WIRE_REACH {
+                p.by_name.get_mut(name).unwrap().wire_reach = Some(reach);
+            }
+            for &(categories, names) in VANILLA_CRAFTING_CATEGORIES {
+                for name in names {
+                    p.by_name.get_mut(*name).unwrap().crafting_categories =
+                        categories.iter().map(|c| c.to_string()).collect();
+                }
+            }
+            p
+        })
+    }
+
+    // Everything with a collision box in a dump of data.raw, e.g. from `factorio --dump-data`
+    // Items, recipes and so on have no collision box and are skipped
+    pub fn from_data_raw(json: &str) -> Result<Self> {
+        let raw: Value = serde_json::from_str(json).map_err(|source| Error::Prototype {
+            path: String::new(),
+            source,
+        })?;
+        let mut ret = Prototypes::default();
+        let types = match raw.as_object() {
+            Some(types) => types,
+            None => return Ok(ret),
+        };
+        for (prototype_type, prototypes) in types {
+            let prototypes = match prototypes.as_object() {
+                Some(p) => p,
+                None => continue,
+            };
+            for (name, proto) in prototypes {
+                if proto.get("collision_box").is_none() {
+                    continue;
+                }
+                let path = format!("{}.{}", prototype_type, name);
+                ret.insert(Prototype::from_data_raw(
+                    prototype_type,
+                    name,
+                    proto,
+                    &path,
+                )?);
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prototype> {
+        self.by_name.get(name)
+    }
+
+    // see Prototype::footprint
+    pub fn footprint(&self, e: &Entity, system: DirectionSystem) -> Result<TileRect> {
+        self.get(&e.name)
+            .map(|p| p.footprint(e, system))
+            .ok_or_else(|| Error::UnknownEntity(e.name.clone()))
+    }
+
+    pub fn insert(&mut self, p: Prototype) -> Option<Prototype> {
+        self.by_name.insert(p.name.clone(), p)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Prototype> {
+        self.by_name.values()
+    }
+}
+
+use self::EntityCategory::*;
+use self::RotationBehaviour::*;
+
+type PrototypeRow = (
+    &'static str,
+    EntityCategory,
+    (f64, f64, f64, f64),
+    (u32, u32),
+    RotationBehaviour,
+);
+
+// name, category, collision box facing north, tiles facing north, rotation
+#[rustfmt::skip]
+static VANILLA_PROTOTYPES: &[PrototypeRow] = &[
+    // belts
+    ("transport-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("fast-transport-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("express-transport-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("underground-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("fast-underground-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("express-underground-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("splitter", Belt, (-0.9, -0.4, 0.9, 0.4), (2, 1), Cardinal),
+    ("fast-splitter", Belt, (-0.9, -0.4, 0.9, 0.4), (2, 1), Cardinal),
+    ("express-splitter", Belt, (-0.9, -0.4, 0.9, 0.4), (2, 1), Cardinal),
+    ("loader", Belt, (-0.4, -0.9, 0.4, 0.9), (1, 2), Cardinal),
+    ("fast-loader", Belt, (-0.4, -0.9, 0.4, 0.9), (1, 2), Cardinal),
+    ("express-loader", Belt, (-0.4, -0.9, 0.4, 0.9), (1, 2), Cardinal),
+    ("turbo-transport-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("turbo-underground-belt", Belt, (-0.4, -0.4, 0.4, 0.4), (1, 1), Cardinal),
+    ("turbo-splitter", Belt, (-0.9, -0.4, 0.9, 0.4), (2, 1), Cardinal),
+    ("turbo-loader", Belt, (-0.4, -0.9, 0.4, 0.9), (1, 2), Cardinal),
+    // inserters
+    ("burner-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("long-handed-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("fast-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("filter-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("stack-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("stack-filter-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    ("bulk-inserter", Inserter, (-0.15, -0.15, 0.15, 0.15), (1, 1), Cardinal),
+    // storage
+    ("wooden-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("iron-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("steel-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("logistic-chest-active-provider", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("logistic-chest-passive-provider", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("logistic-chest-storage", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("logistic-chest-buffer", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("logistic-chest-requester", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("active-provider-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("passive-provider-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("storage-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("buffer-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("requester-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("infinity-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    ("linked-chest", Storage, (-0.35, -0.35, 0.35, 0.35), (1, 1), Fixed),
+    // fluids
+    ("pipe", Fluid, (-0.29, -0.29, 0.29, 0.29), (1, 1), Fixed),
+    ("infinity-pipe", Fluid, (-0.29, -0.29, 0.29, 0.29), (1, 1), Fixed),
+    ("pipe-to-ground", Fluid, (-0.29, -0.29, 0.29, 0.2), (1, 1), Cardinal),
+    ("pump", Fluid, (-0.29, -0.9, 0.29, 0.9), (1, 2), Cardinal),
+    ("storage-tank", Fluid, (-1.3, -1.3, 1.3, 1.3), (3, 3), Cardinal),
+    ("offshore-pump", Fluid, (-0.4, -1.05, 0.4, 0.3), (1, 2), Cardinal),
+    // power
+    ("small-electric-pole", Power, (-0.15, -0.15, 0.15, 0.15), (1, 1), Fixed),
+    ("medium-electric-pole", Power, (-0.15, -0.15, 0.15, 0.15), (1, 1), Fixed),
+    ("big-electric-pole", Power, (-0.65, -0.65, 0.65, 0.65), (2, 2), Fixed),
+    ("substation", Power, (-0.7, -0.7, 0.7, 0.7), (2, 2), Fixed),
+    ("boiler", Power, (-1.29, -0.79, 1.29, 0.79), (3, 2), Cardinal),
+    ("heat-exchanger", Power, (-1.29, -0.79, 1.29, 0.79), (3, 2), Cardinal),
+    ("steam-engine", Power, (-1.35, -2.35, 1.35, 2.35), (3, 5), Cardinal),
+    ("steam-turbine", Power, (-1.35, -2.35, 1.35, 2.35), (3, 5), Cardinal),
+    ("solar-panel", Power, (-1.4, -1.4, 1.4, 1.4), (3, 3), Fixed),
+    ("accumulator", Power, (-0.9, -0.9, 0.9, 0.9), (2, 2), Fixed),
+    ("nuclear-reactor", Power, (-2.2, -2.2, 2.2, 2.2), (5, 5), Fixed),
+    ("heat-pipe", Power, (-0.3, -0.3, 0.3, 0.3), (1, 1), Fixed),
+    ("heat-interface", Power, (-0.4, -0.4, 0.4, 0.4), (1, 1), Fixed),
+    ("electric-energy-interface", Power, (-0.9, -0.9, 0.9, 0.9), (2, 2), Fixed),
+    ("power-switch", Power, (-0.7, -0.7, 0.7, 0.7), (2, 2), Fixed),
+    ("fusion-reactor", Power, (-2.9, -2.9, 2.9, 2.9), (6, 6), Cardinal),
+    ("fusion-generator", Power, (-1.4, -2.4, 1.4, 2.4), (3, 5), Cardinal),
+    ("heating-tower", Power, (-1.25, -1.25, 1.25, 1.25), (3, 3), Fixed),
+    ("lightning-rod", Power, (-0.2, -0.2, 0.2, 0.2), (1, 1), Fixed),
+    ("lightning-collector", Power, (-0.7, -0.7, 0.7, 0.7), (2, 2), Fixed),
+    // production
+    ("burner-mining-drill", Production, (-0.7, -0.7, 0.7, 0.7), (2, 2), Cardinal),
+    ("electric-mining-drill", Production, (-1.4, -1.4, 1.4, 1.4), (3, 3), Cardinal),
+    ("pumpjack", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Cardinal),
+    ("stone-furnace", Production, (-0.7, -0.7, 0.7, 0.7), (2, 2), Fixed),
+    ("steel-furnace", Production, (-0.875, -0.875, 0.875, 0.875), (2, 2), Fixed),
+    ("electric-furnace", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Fixed),
+    ("assembling-machine-1", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Cardinal),
+    ("assembling-machine-2", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Cardinal),
+    ("assembling-machine-3", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Cardinal),
+    ("oil-refinery", Production, (-2.4, -2.4, 2.4, 2.4), (5, 5), Cardinal),
+    ("chemical-plant", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Cardinal),
+    ("centrifuge", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Fixed),
+    ("lab", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Fixed),
+    ("beacon", Production, (-1.2, -1.2, 1.2, 1.2), (3, 3), Fixed),
+    ("rocket-silo", Production, (-4.4, -4.4, 4.4, 4.4), (9, 9), Fixed),
+    ("big-mining-drill", Production, (-2.3, -2.3, 2.3, 2.3), (5, 5), Cardinal),
+    ("foundry", Production, (-2.2, -2.2, 2.2, 2.2), (5, 5), Cardinal),
+    ("electromagnetic-plant", Production, (-1.7, -1.7, 1.7, 1.7), (4, 4), Cardinal),
+    ("cryogenic-plant", Production, (-2.2, -2.2, 2.2, 2.2), (5, 5), Cardinal),
+    ("biochamber", Production, (-1.3, -1.3, 1.3, 1.3), (3, 3), Cardinal),
+    ("recycler", Production, (-0.7, -1.7, 0.7, 1.7), (2, 4), Cardinal),
+    ("crusher", Production, (-0.7, -1.2, 0.7, 1.2), (2, 3), Cardinal),
+    ("biolab", Production, (-2.2, -2.2, 2.2, 2.2), (5, 5), Fixed),
+    ("agricultural-tower", Production, (-1.4, -1.4, 1.4, 1.4), (3, 3), Fixed),
+    ("captive-biter-spawner", Production, (-2.2, -2.2, 2.2, 2.2), (5, 5), Fixed),
+    ("asteroid-collector", Production, (-1.4, -1.4, 1.4, 1.4), (3, 3), Cardinal),
+    // circuit network
+    ("arithmetic-combinator", Circuit, (-0.35, -0.65, 0.35, 0.65), (1, 2), Cardinal),
+    ("decider-combinator", Circuit, (-0.35, -0.65, 0.35, 0.65), (1, 2), Cardinal),
+    ("selector-combinator", Circuit, (-0.35, -0.65, 0.35, 0.65), (1, 2), Cardinal),
+    ("constant-combinator", Circuit, (-0.35, -0.35, 0.35, 0.35), (1, 1), Cardinal),
+    ("programmable-speaker", Circuit, (-0.3, -0.3, 0.3, 0.3), (1, 1), Fixed),
+    ("display-panel", Circuit, (-0.35, -0.35, 0.35, 0.35), (1, 1), Cardinal),
+    ("small-lamp", Circuit, (-0.15, -0.15, 0.15, 0.15), (1, 1), Fixed),
+    // rails and trains
+    ("straight-rail", Rail, (-0.7, -0.99, 0.7, 0.99), (2, 2), AllHeadings),
+    ("curved-rail", Rail, (-0.75, -3.8, 0.75, 3.8), (4, 8), CurvedRail),
+    ("half-diagonal-rail", Rail, (-0.75, -1.9, 0.75, 1.9), (2, 4), AllHeadings),
+    ("curved-rail-a", Rail, (-0.75, -2.6, 0.75, 2.6), (2, 6), AllHeadings),
+    ("curved-rail-b", Rail, (-0.75, -1.9, 0.75, 1.9), (4, 4), AllHeadings),
+    ("legacy-straight-rail", Rail, (-0.7, -0.99, 0.7, 0.99), (2, 2), AllHeadings),
+    ("legacy-curved-rail", Rail, (-0.75, -3.8, 0.75, 3.8), (4, 8), CurvedRail),
+    ("elevated-straight-rail", Rail, (-0.7, -0.99, 0.7, 0.99), (2, 2), AllHeadings),
+    ("elevated-half-diagonal-rail", Rail, (-0.75, -1.9, 0.75, 1.9), (2, 4), AllHeadings),
+    ("elevated-curved-rail-a", Rail, (-0.75, -2.6, 0.75, 2.6), (2, 6), AllHeadings),
+    ("elevated-curved-rail-b", Rail, (-0.75, -1.9, 0.75, 1.9), (4, 4), AllHeadings),
+    ("rail-ramp", Rail, (-1.6, -7.6, 1.6, 7.6), (4, 16), AllHeadings),
+    ("rail-support", Rail, (-1.9, -1.9, 1.9, 1.9), (4, 4), AllHeadings),
+    ("rail-signal", Rail, (-0.2, -0.2, 0.2, 0.2), (1, 1), AllHeadings),
+    ("rail-chain-signal", Rail, (-0.2, -0.2, 0.2, 0.2), (1, 1), AllHeadings),
+    ("train-stop", Rail, (-0.5, -0.5, 0.5, 0.5), (2, 2), Cardinal),
+    ("locomotive", RollingStock, (-0.6, -2.6, 0.6, 2.6), (2, 6), Orientation),
+    ("cargo-wagon", RollingStock, (-0.6, -2.4, 0.6, 2.4), (2, 6), Orientation),
+    ("fluid-wagon", RollingStock, (-0.6, -2.4, 0.6, 2.4), (2, 6), Orientation),
+    ("artillery-wagon", RollingStock, (-0.6, -2.4, 0.6, 2.4), (2, 6), Orientation),
+    // defense
+    ("stone-wall", Defense, (-0.49, -0.49, 0.49, 0.49), (1, 1), Fixed),
+    ("gate", Defense, (-0.29, -0.29, 0.29, 0.29), (1, 1), Cardinal),
+    ("land-mine", Defense, (-0.4, -0.4, 0.4, 0.4), (1, 1), Fixed),
+    ("gun-turret", Defense, (-0.95, -0.95, 0.95, 0.95), (2, 2), Fixed),
+    ("laser-turret", Defense, (-0.95, -0.95, 0.95, 0.95), (2, 2), Fixed),
+    ("flamethrower-turret", Defense, (-0.7, -1.2, 0.7, 1.2), (2, 3), Cardinal),
+    ("artillery-turret", Defense, (-1.45, -1.45, 1.45, 1.45), (3, 3), Fixed),
+    ("radar", Defense, (-1.4, -1.4, 1.4, 1.4), (3, 3), Fixed),
+    ("rocket-turret", Defense, (-1.2, -1.2, 1.2, 1.2), (3, 3), Fixed),
+    ("tesla-turret", Defense, (-0.7, -0.7, 0.7, 0.7), (2, 2), Fixed),
+    ("railgun-turret", Defense, (-1.7, -1.7, 1.7, 1.7), (4, 4), Fixed),
+    // logistics network
+    ("roboport", Logistics, (-1.7, -1.7, 1.7, 1.7), (4, 4), Fixed),
+    // space
+    ("cargo-landing-pad", Storage, (-3.9, -3.9, 3.9, 3.9), (8, 8), Fixed),
+    ("cargo-bay", Other, (-1.9, -1.9, 1.9, 1.9), (4, 4), Fixed),
+    ("space-platform-hub", Other, (-3.9, -3.9, 3.9, 3.9), (8, 8), Fixed),
+    ("thruster", Other, (-2.9, -4.9, 2.9, 4.9), (6, 10), Fixed),
+];
+
+static VANILLA_REPLACEABLE_GROUPS: &[(&str, &[&str])] = &[
+    (
+        "transport-belt",
+        &[
+            "transport-belt",
+            "fast-transport-belt",
+            "express-transport-belt",
+            "turbo-transport-belt",
+        ],
+    ),
+    (
+        "underground-belt",
+        &[
+            "underground-belt",
+            "fast-underground-belt",
+            "express-underground-belt",
+            "turbo-underground-belt",
+        ],
+    ),
+    (
+        "splitter",
+        &[
+            "splitter",
+            "fast-splitter",
+            "express-splitter",
+            "turbo-splitter",
+        ],
+    ),
+    (
+        "loader",
+        &["loader", "fast-loader", "express-loader", "turbo-loader"],
+    ),
+    (
+        "inserter",
+        &[
+            "burner-inserter",
+            "inserter",
+            "long-handed-inserter",
+            "fast-inserter",
+            "filter-inserter",
+            "stack-inserter",
+            "stack-filter-inserter",
+            "bulk-inserter",
+        ],
+    ),
+    ("container", &["wooden-chest", "iron-chest", "steel-chest"]),
+    (
+        "logistic-container",
+        &[
+            "logistic-chest-active-provider",
+            "logistic-chest-passive-provider",
+            "logistic-chest-storage",
+            "logistic-chest-buffer",
+            "logistic-chest-requester",
+            "active-provider-chest",
+            "passive-provider-chest",
+            "storage-chest",
+            "buffer-chest",
+            "requester-chest",
+        ],
+    ),
+    ("pipe", &["pipe"]),
+    ("pipe-to-ground", &["pipe-to-ground"]),
+    ("furnace", &["stone-furnace", "steel-furnace"]),
+    (
+        "assembling-machine",
+        &[
+            "assembling-machine-1",
+            "assembling-machine-2",
+            "assembling-machine-3",
+        ],
+    ),
+    (
+        "electric-pole",
+        &["small-electric-pole", "medium-electric-pole"],
+    ),
+    ("wall", &["stone-wall"]),
+    ("mining-drill", &["burner-mining-drill"]),
+];
+
+static VANILLA_WIRE_REACH: &[(&str, f64)] = &[
+    ("small-electric-pole", 7.5),
+    ("medium-electric-pole", 9.0),
+    ("big-electric-pole", 30.0),
+    ("substation", 18.0),
+];
+
+static VANILLA_BELT_SPEEDS: &[(f64, &[&str])] = &[
+    (
+        0.03125,
+        &["transport-belt", "underground-belt", "splitter", "loader"],
+    ),
+    (
+        0.0625,
+        &[
+            "fast-transport-belt",
+            "fast-underground-belt",
+            "fast-splitter",
+            "fast-loader",
+        ],
+    ),
+    (
+        0.09375,
+        &[
+            "express-transport-belt",
+            "express-underground-belt",
+            "express-splitter",
+            "express-loader",
+        ],
+    ),
+    (
+        0.125,
+        &[
+            "turbo-transport-belt",
+            "turbo-underground-belt",
+            "turbo-splitter",
+            "turbo-loader",
+        ],
+    ),
+];
+
+static VANILLA_CRAFTING_CATEGORIES: &[(&[&str], &[&str])] = &[
+    (
+        &["crafting", "basic-crafting", "advanced-crafting"],
+        &["assembling-machine-1"],
+    ),
+    (
+        &[
+            "crafting",
+            "basic-crafting",
+            "advanced-crafting",
+            "crafting-with-fluid",
+        ],
+        &["assembling-machine-2", "assembling-machine-3"],
+    ),
+    (
+        &["smelting"],
+        &["stone-furnace", "steel-furnace", "electric-furnace"],
+    ),
+    (&["chemistry"], &["chemical-plant"]),
+    (&["oil-processing"], &["oil-refinery"]),
+    (&["centrifuging"], &["centrifuge"]),
+    (&["rocket-building"], &["rocket-silo"]),
+    (
+        &[
+            "metallurgy",
+            "pressing",
+            "crafting-with-fluid-or-metallurgy",
+        ],
+        &["foundry"],
+    ),
+    (
+        &["electromagnetics", "electronics", "electronics-with-fluid"],
+        &["electromagnetic-plant"],
+    ),
+    (
+        &["cryogenics", "chemistry-or-cryogenics"],
+        &["cryogenic-plant"],
+    ),
+    (
+        &["organic", "organic-or-assembling", "organic-or-chemistry"],
+        &["biochamber"],
+    ),
+    (&["recycling"], &["recycler"]),
+    (&["crushing"], &["crusher"]),
+];
+
 #[derive(Debug)]
 pub struct EntityShape {
     pub name: String,
@@ -33,52 +782,273 @@ pub struct EntityShape {
 }
 
 impl EntityShape {
-    pub fn from(e: &Entity) -> Result<Self, String> {
-        let entity_type = EntityType::from_str(&e.name)?;
-        match entity_type {
-            EntityType::TransportBelt => {
-                let raw_pos = e.position;
-                let corner = Point::new(raw_pos.x, raw_pos.y);
-                let exterior = LineString(vec![
-                    corner,
-                    Point::new(raw_pos.x, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                ]);
-                Ok(EntityShape {
-                    name: "transport-belt".into(),
-                    shape: Polygon::new(exterior.clone(), vec![]),
-                })
-            }
-            // TODO this isn't a single unit square
-            EntityType::Splitter => {
-                let raw_pos = e.position;
-                let corner = Point::new(raw_pos.x, raw_pos.y);
-                let exterior = LineString(vec![
-                    corner,
-                    Point::new(raw_pos.x, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                ]);
-                Ok(EntityShape {
-                    name: "splitter".into(),
-                    shape: Polygon::new(exterior.clone(), vec![]),
-                })
-            }
-            EntityType::UndergroundBelt => {
-                let raw_pos = e.position;
-                let corner = Point::new(raw_pos.x, raw_pos.y);
-                let exterior = LineString(vec![
-                    corner,
-                    Point::new(raw_pos.x, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                    Point::new(raw_pos.x + 1.0, raw_pos.y + 1.0),
-                ]);
-                Ok(EntityShape {
-                    name: "underground-belt".into(),
-                    shape: Polygon::new(exterior.clone(), vec![]),
-                })
-            }
+    // direction numbers mean different things depending on the blueprint's version, hence the system
+    pub fn from(e: &Entity, system: DirectionSystem) -> Result<Self> {
+        EntityShape::with_prototypes(e, system, Prototypes::vanilla())
+    }
+
+    pub fn with_prototypes(
+        e: &Entity,
+        system: DirectionSystem,
+        prototypes: &Prototypes,
+    ) -> Result<Self> {
+        let proto = prototypes
+            .get(&e.name)
+            .ok_or_else(|| Error::UnknownEntity(e.name.clone()))?;
+        let turns = proto.turns(e, system);
+        let mirrored = proto.mirrored(e, system);
+        let (x, y) = e.position.to_f64();
+        let mut ring: Vec<Point<f64>> = proto
+            .collision_box
+            .corners()
+            .iter()
+            .map(|&(cx, cy)| {
+                let c = if mirrored { (-cx, cy) } else { (cx, cy) };
+                let (dx, dy) = rotate(c, turns);
+                Point::new(x + dx, y + dy)
+            })
+            .collect();
+        let first = ring[0];
+        ring.push(first); // geo wants the ring closed
+        Ok(EntityShape {
+            name: e.name.clone(),
+            shape: Polygon::new(LineString(ring), vec![]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn entity(json: &str) -> ::types::Entity {
+        ::serde_json::from_str(json).unwrap()
+    }
+
+    fn extent(shape: &super::EntityShape) -> (f64, f64, f64, f64) {
+        let xs = shape.shape.exterior.0.iter().map(|p| p.x());
+        let ys = shape.shape.exterior.0.iter().map(|p| p.y());
+        (
+            xs.clone().fold(f64::MAX, f64::min),
+            ys.clone().fold(f64::MAX, f64::min),
+            xs.fold(f64::MIN, f64::max),
+            ys.fold(f64::MIN, f64::max),
+        )
+    }
+
+    fn assert_extent(shape: &super::EntityShape, want: (f64, f64, f64, f64)) {
+        let got = extent(shape);
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(
+            close(got.0, want.0)
+                && close(got.1, want.1)
+                && close(got.2, want.2)
+                && close(got.3, want.3),
+            "{:?} != {:?}",
+            got,
+            want
+        );
+    }
+
+    #[test]
+    fn test_vanilla_boxes_fit_their_tiles() {
+        use super::Prototypes;
+
+        let vanilla = Prototypes::vanilla();
+        assert!(vanilla.len() > 90);
+        for p in vanilla.iter() {
+            assert!(
+                p.collision_box.width() <= f64::from(p.tile_width),
+                "{} is wider than its tiles",
+                p.name
+            );
+            assert!(
+                p.collision_box.height() <= f64::from(p.tile_height),
+                "{} is taller than its tiles",
+                p.name
+            );
         }
     }
+
+    #[test]
+    fn test_rotated_splitter_shape() {
+        use super::EntityShape;
+        use types::DirectionSystem;
+
+        let north = entity(r#"{"entity_number":1,"name":"splitter","position":{"x":1,"y":0.5}}"#);
+        let shape = EntityShape::from(&north, DirectionSystem::Eight).unwrap();
+        assert_extent(&shape, (0.1, 0.1, 1.9, 0.9));
+        assert_eq!(shape.shape.exterior.0.len(), 5);
+
+        let east = entity(
+            r#"{"entity_number":1,"name":"splitter","position":{"x":0.5,"y":1},"direction":2}"#,
+        );
+        let shape = EntityShape::from(&east, DirectionSystem::Eight).unwrap();
+        assert_extent(&shape, (0.1, 0.1, 0.9, 1.9));
+
+        // the same heading is 4 in 2.0
+        let east = entity(
+            r#"{"entity_number":1,"name":"splitter","position":{"x":0.5,"y":1},"direction":4}"#,
+        );
+        let shape = EntityShape::from(&east, DirectionSystem::Sixteen).unwrap();
+        assert_extent(&shape, (0.1, 0.1, 0.9, 1.9));
+    }
+
+    #[test]
+    fn test_asymmetric_and_half_tile_shapes() {
+        use super::{EntityShape, Prototypes};
+        use types::DirectionSystem;
+
+        // odd sized entities sit on half tiles
+        let assembler = entity(
+            r#"{"entity_number":1,"name":"assembling-machine-2","position":{"x":1.5,"y":-0.5}}"#,
+        );
+        let shape = EntityShape::from(&assembler, DirectionSystem::Eight).unwrap();
+        assert_extent(&shape, (0.3, -1.7, 2.7, 0.7));
+
+        // the pump's box isn't centred, so facing south flips it
+        let pump = entity(
+            r#"{"entity_number":1,"name":"offshore-pump","position":{"x":0.5,"y":0},"direction":4}"#,
+        );
+        let shape = EntityShape::from(&pump, DirectionSystem::Eight).unwrap();
+        assert_extent(&shape, (0.1, -0.3, 0.9, 1.05));
+        let proto = Prototypes::vanilla().get("offshore-pump").unwrap();
+        assert_eq!(proto.tile_size(0.25), (2, 1));
+    }
+
+    #[test]
+    fn test_curved_rail_footprints() {
+        use super::Prototypes;
+        use types::DirectionSystem;
+
+        // 1.1 curves come in mirrored pairs, 0 and 1 both run north-south
+        let proto = Prototypes::vanilla().get("curved-rail").unwrap();
+        for d in 0..8 {
+            let rail = entity(&format!(
+                r#"{{"entity_number":1,"name":"curved-rail","position":{{"x":0,"y":0}},"direction":{}}}"#,
+                d
+            ));
+            let size = proto.tile_size(proto.turns(&rail, DirectionSystem::Eight));
+            let expected = if d % 4 < 2 { (4, 8) } else { (8, 4) };
+            assert_eq!(size, expected, "direction {}", d);
+            assert_eq!(
+                proto.mirrored(&rail, DirectionSystem::Eight),
+                d % 2 == 1,
+                "direction {}",
+                d
+            );
+        }
+    }
+
+    #[test]
+    fn test_rolling_stock_orientation() {
+        use super::EntityShape;
+        use types::DirectionSystem;
+
+        let wagon = entity(
+            r#"{"entity_number":1,"name":"cargo-wagon","position":{"x":0,"y":0},"orientation":0.125}"#,
+        );
+        let shape = EntityShape::from(&wagon, DirectionSystem::Eight).unwrap();
+        // a diagonal wagon reaches 3.0 / sqrt(2) either way of its centre
+        let half = 3.0 / 2f64.sqrt();
+        assert_extent(&shape, (-half, -half, half, half));
+    }
+
+    #[test]
+    fn test_unknown_entity() {
+        use super::EntityShape;
+        use error::Error;
+        use types::DirectionSystem;
+
+        let e = entity(r#"{"entity_number":1,"name":"spaceship","position":{"x":0,"y":0}}"#);
+        match EntityShape::from(&e, DirectionSystem::Eight) {
+            Err(Error::UnknownEntity(ref name)) => assert_eq!(name, "spaceship"),
+            other => panic!("expected an unknown entity, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_prototypes_from_data_raw() {
+        use super::{EntityCategory, EntityShape, Prototypes, RotationBehaviour};
+        use types::DirectionSystem;
+
+        let data_raw = r#"{
+            "transport-belt": {"turbo-belt": {"name": "turbo-belt", "type": "transport-belt", "collision_box": [[-0.4, -0.4], [0.4, 0.4]], "selection_box": [[-0.5, -0.5], [0.5, 0.5]], "speed": 0.125, "fast_replaceable_group": "transport-belt", "flags": {}}},
+            "assembling-machine": {"big-assembler": {"name": "big-assembler", "type": "assembling-machine", "collision_box": [[-1.9, -2.9], [1.9, 2.9]], "crafting_categories": ["crafting", "big-crafting"]}},
+            "container": {"crate": {"name": "crate", "type": "container", "collision_box": [[-0.35, -0.35], [0.35, 0.35]], "flags": ["placeable-neutral", "not-rotatable"]}},
+            "electric-pole": {"tall-pole": {"name": "tall-pole", "type": "electric-pole", "collision_box": [[-0.15, -0.15], [0.15, 0.15]], "maximum_wire_distance": 12}},
+            "item": {"turbo-belt": {"name": "turbo-belt", "type": "item", "stack_size": 100}}
+        }"#;
+        let protos = Prototypes::from_data_raw(data_raw).unwrap();
+        assert_eq!(protos.len(), 4);
+        assert_eq!(protos.get("tall-pole").unwrap().wire_reach, Some(12.0));
+
+        let belt = protos.get("turbo-belt").unwrap();
+        assert_eq!(belt.category, EntityCategory::Belt);
+        assert_eq!(belt.rotation, RotationBehaviour::Cardinal);
+        assert_eq!(belt.belt_speed, Some(0.125));
+        assert_eq!(belt.fast_replaceable_group, Some("transport-belt".into()));
+        assert!(belt.selection_box.is_some());
+
+        let assembler = protos.get("big-assembler").unwrap();
+        assert_eq!((assembler.tile_width, assembler.tile_height), (4, 6));
+        assert_eq!(
+            assembler.crafting_categories,
+            vec!["crafting", "big-crafting"]
+        );
+        let e = entity(
+            r#"{"entity_number":1,"name":"big-assembler","position":{"x":0,"y":0},"direction":2}"#,
+        );
+        let shape = EntityShape::with_prototypes(&e, DirectionSystem::Eight, &protos).unwrap();
+        assert_extent(&shape, (-2.9, -1.9, 2.9, 1.9));
+        // not in the dump, so not in the registry, even though vanilla knows it
+        assert!(EntityShape::with_prototypes(
+            &entity(r#"{"entity_number":1,"name":"inserter","position":{"x":0,"y":0}}"#),
+            DirectionSystem::Eight,
+            &protos
+        )
+        .is_err());
+
+        assert_eq!(
+            protos.get("crate").unwrap().rotation,
+            RotationBehaviour::Fixed
+        );
+    }
+
+    #[test]
+    fn test_data_raw_error_path() {
+        use super::Prototypes;
+
+        let data_raw =
+            r#"{"wall": {"fence": {"name": "fence", "collision_box": [[-0.5, -0.5], "wide"]}}}"#;
+        let err = Prototypes::from_data_raw(data_raw).unwrap_err();
+        assert!(
+            err.to_string().contains("wall.fence.collision_box"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_vanilla_extras() {
+        use super::Prototypes;
+
+        let vanilla = Prototypes::vanilla();
+        assert_eq!(
+            vanilla.get("fast-splitter").unwrap().belt_speed,
+            Some(0.0625)
+        );
+        assert_eq!(
+            vanilla.get("big-electric-pole").unwrap().wire_reach,
+            Some(30.0)
+        );
+        assert_eq!(vanilla.get("fast-splitter").unwrap().wire_reach, None);
+        assert_eq!(
+            vanilla.get("bulk-inserter").unwrap().fast_replaceable_group,
+            Some("inserter".into())
+        );
+        assert!(vanilla
+            .get("chemical-plant")
+            .unwrap()
+            .crafting_categories
+            .contains(&"chemistry".to_string()));
+    }
 }
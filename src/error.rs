@@ -0,0 +1,148 @@
+// One error type for everything that can go wrong between a blueprint string and our structs
+// Nothing in the public API should panic on bad input - it should hand one of these back instead
+
+use base64::DecodeError;
+use serde_json;
+use serde_path_to_error;
+use std::{error, fmt, io, result};
+use types::Schema;
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    // the string had nothing in it
+    Empty,
+    // the leading version byte isn't one we know how to read
+    UnknownVersion(char),
+    // a character outside the base64 alphabet - offset is into the full blueprint string
+    InvalidBase64 {
+        offset: usize,
+        byte: u8,
+    },
+    // the base64 payload has a length that can't be decoded
+    InvalidBase64Length(usize),
+    // the zlib stream ended before it was complete - usually a string that got cut off in a paste
+    Truncated,
+    // any other problem inflating or deflating the payload
+    Zlib(io::Error),
+    // the JSON didn't fit our structs - path is e.g. "blueprint.entities[12].position.x"
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    // a prototype name we don't have any data for
+    UnknownEntity(String),
+    // a data.raw dump that didn't parse - path is e.g. "inserter.fast-inserter.collision_box"
+    Prototype {
+        path: String,
+        source: serde_json::Error,
+    },
+    // asked for a single blueprint, got a book or planner
+    NotABlueprint,
+    // a replacement that wouldn't fit where the original was
+    SizeMismatch {
+        from: String,
+        to: String,
+    },
+    // blueprints in different layouts that can't go together until one is migrated
+    SchemaMismatch(Schema, Schema),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "blueprint string is empty"),
+            Error::UnknownVersion(c) => write!(f, "unknown blueprint string version {:?}", c),
+            Error::InvalidBase64 { offset, byte } => write!(
+                f,
+                "invalid base64 byte {:?} at offset {}",
+                *byte as char, offset
+            ),
+            Error::InvalidBase64Length(len) => {
+                write!(f, "invalid base64 length {}", len)
+            }
+            Error::Truncated => write!(f, "compressed blueprint data is truncated"),
+            Error::Zlib(e) => write!(f, "could not decompress blueprint: {}", e),
+            Error::Json { path, source } => {
+                if path.is_empty() || path == "." {
+                    write!(f, "invalid blueprint JSON: {}", source)
+                } else {
+                    write!(f, "invalid blueprint JSON at {}: {}", path, source)
+                }
+            }
+            Error::UnknownEntity(name) => write!(f, "unknown entity type {:?}", name),
+            Error::Prototype { path, source } => {
+                if path.is_empty() {
+                    write!(f, "invalid prototype data: {}", source)
+                } else {
+                    write!(f, "invalid prototype data at {}: {}", path, source)
+                }
+            }
+            Error::NotABlueprint => write!(f, "expected a single blueprint"),
+            Error::SizeMismatch { from, to } => write!(
+                f,
+                "can't replace {:?} with {:?}, they aren't the same size",
+                from, to
+            ),
+            Error::SchemaMismatch(a, b) => write!(
+                f,
+                "can't combine {:?} and {:?} blueprints, migrate one first",
+                a, b
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Zlib(e) => Some(e),
+            Error::Json { source, .. } | Error::Prototype { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Truncated,
+            _ => Error::Zlib(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json {
+            path: String::new(),
+            source: e,
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        Error::Json {
+            path: e.path().to_string(),
+            source: e.into_inner(),
+        }
+    }
+}
+
+impl Error {
+    // base64 offsets come back relative to the payload, to_original maps them back onto the input
+    pub(crate) fn from_base64<F>(e: DecodeError, to_original: F, payload_len: usize) -> Self
+    where
+        F: Fn(usize) -> usize,
+    {
+        match e {
+            DecodeError::InvalidByte(offset, byte) => Error::InvalidBase64 {
+                offset: to_original(offset),
+                byte,
+            },
+            DecodeError::InvalidLength => Error::InvalidBase64Length(payload_len),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+// Test fixtures shared by every module's tests
+
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+use types::{Blueprint, Container};
+
+// the sample blueprint string from resource/
+pub fn balancer() -> String {
+    let sample_bp_f =
+        File::open(Path::new("./resource/balancer.txt")).expect("Could not open balancer.txt");
+    let mut bp_reader = BufReader::new(sample_bp_f);
+    let mut bp_string = String::new();
+    bp_reader
+        .read_to_string(&mut bp_string)
+        .expect("Could not read balancer.txt");
+    bp_string
+}
+
+// a blueprint from the fields inside it, e.g. r#""entities":[...]"# - item and version are filled in
+pub fn blueprint(version: u64, body: &str) -> Blueprint {
+    let json = format!(
+        r#"{{"blueprint":{{{},"item":"blueprint","version":{}}}}}"#,
+        body, version
+    );
+    match ::serde_json::from_str(&json).unwrap() {
+        Container::Blueprint(bp) => bp,
+        _ => panic!("expected a blueprint"),
+    }
+}
@@ -9,7 +9,18 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate serde_path_to_error;
 
 pub mod blueprint;
+pub mod compose;
 pub mod entities;
-mod types;
+mod error;
+#[cfg(test)]
+mod fixtures;
+pub mod migrate;
+pub mod spatial;
+pub mod transform;
+pub mod types;
+pub mod validate;
+
+pub use error::{Error, Result};
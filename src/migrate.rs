@@ -0,0 +1,609 @@
+// Bringing old blueprints up to date - prototype renames, and the 2.0 layout changes
+// Anything that can't be converted faithfully is left alone and noted in the report
+
+use error::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{self, Value};
+use std::fmt;
+use types::*;
+
+pub const V0_17: MapVersion = MapVersion::new(0, 17, 0, 0);
+pub const V1_1: MapVersion = MapVersion::new(1, 1, 0, 0);
+pub const V2_0: MapVersion = MapVersion::new(2, 0, 0, 0);
+
+// Prototypes renamed in each release, applied to anything from before it
+static RENAMES_0_17: &[(&str, &str)] = &[("raw-wood", "wood")];
+static RENAMES_2_0: &[(&str, &str)] = &[
+    ("stack-inserter", "bulk-inserter"),
+    ("stack-filter-inserter", "bulk-inserter"),
+    ("filter-inserter", "fast-inserter"),
+    ("logistic-chest-active-provider", "active-provider-chest"),
+    ("logistic-chest-passive-provider", "passive-provider-chest"),
+    ("logistic-chest-storage", "storage-chest"),
+    ("logistic-chest-buffer", "buffer-chest"),
+    ("logistic-chest-requester", "requester-chest"),
+    ("effectivity-module", "efficiency-module"),
+    ("effectivity-module-2", "efficiency-module-2"),
+    ("effectivity-module-3", "efficiency-module-3"),
+];
+
+fn renames() -> Vec<(MapVersion, &'static [(&'static str, &'static str)])> {
+    vec![(V0_17, RENAMES_0_17), (V2_0, RENAMES_2_0)]
+}
+
+// Renamed into something that already existed - a blueprint keeps the difference with use_filters,
+// but a planner that picked these out would pick out every fast or bulk inserter instead
+static MERGED_2_0: &[&str] = &["filter-inserter", "stack-filter-inserter"];
+
+// Something the migration couldn't do by itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigrationIssue {
+    pub blueprint: Option<String>, // label of the blueprint or planner it's in
+    pub entity_number: Option<i32>,
+    pub name: Option<String>, // prototype involved, if any
+    pub reason: String,
+}
+
+impl fmt::Display for MigrationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.blueprint.clone().unwrap_or_else(|| "Untitled".into())
+        )?;
+        if let Some(n) = self.entity_number {
+            write!(f, " #{}", n)?;
+        }
+        if let Some(ref name) = self.name {
+            write!(f, " ({})", name)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MigrationReport {
+    pub issues: Vec<MigrationIssue>,
+}
+
+impl MigrationReport {
+    // true if everything made it across
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn note(&mut self, label: &Option<String>, e: Option<&Entity>, reason: &str) {
+        self.issues.push(MigrationIssue {
+            blueprint: label.clone(),
+            entity_number: e.map(|e| e.entity_number),
+            name: e.map(|e| e.name.clone()),
+            reason: reason.into(),
+        });
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "migrated cleanly");
+        }
+        writeln!(f, "{} issue(s):", self.issues.len())?;
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+// Rewrite everything in c that's older than target so it reads as if target had written it
+// Books are migrated item by item, and anything newer than target is left as it is
+pub fn migrate(c: &mut Container, target: MapVersion) -> Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+    migrate_container(c, target, &mut report)?;
+    Ok(report)
+}
+
+fn migrate_container(
+    c: &mut Container,
+    target: MapVersion,
+    report: &mut MigrationReport,
+) -> Result<()> {
+    match c {
+        Container::Blueprint(bp) => migrate_blueprint(bp, target, report),
+        Container::BlueprintBook(book) => {
+            for entry in book.blueprints.iter_mut() {
+                migrate_container(&mut entry.content, target, report)?;
+            }
+            if migrates_forward(book.version, target, &book.label, report) {
+                book.version = target;
+            }
+            Ok(())
+        }
+        Container::UpgradePlanner(up) => {
+            if migrates_forward(up.version, target, &up.label, report) {
+                note_merged_names(&*up, &up.label, up.version, target, report)?;
+                rename_prototypes(up, up.version, target)?;
+                up.version = target;
+            }
+            Ok(())
+        }
+        Container::DeconstructionPlanner(dp) => {
+            if migrates_forward(dp.version, target, &dp.label, report) {
+                note_merged_names(&*dp, &dp.label, dp.version, target, report)?;
+                rename_prototypes(dp, dp.version, target)?;
+                dp.version = target;
+            }
+            Ok(())
+        }
+    }
+}
+
+// false if there's nothing to do - notes it when we'd have to go backwards
+fn migrates_forward(
+    from: MapVersion,
+    target: MapVersion,
+    label: &Option<String>,
+    report: &mut MigrationReport,
+) -> bool {
+    if from > target {
+        report.note(
+            label,
+            None,
+            &format!("made in {}, can't migrate back to {}", from, target),
+        );
+    }
+    from < target
+}
+
+fn migrate_blueprint(
+    bp: &mut Blueprint,
+    target: MapVersion,
+    report: &mut MigrationReport,
+) -> Result<()> {
+    let from = bp.version;
+    if !migrates_forward(from, target, &bp.label, report) {
+        return Ok(());
+    }
+    let to_v2 =
+        Schema::for_version(from) == Schema::Legacy && Schema::for_version(target) == Schema::V2;
+
+    // filter inserters become ordinary inserters with filtering switched on - flag them before the rename
+    if to_v2 {
+        for e in bp.entities.iter_mut() {
+            if e.name == "filter-inserter" || e.name == "stack-filter-inserter" {
+                e.extra.insert("use_filters".into(), Value::Bool(true));
+            }
+        }
+    }
+
+    rename_prototypes(bp, from, target)?;
+
+    if to_v2 {
+        upgrade_to_v2(bp, report)?;
+    }
+    bp.version = target;
+    Ok(())
+}
+
+// The structural side of 1.1 -> 2.0
+fn upgrade_to_v2(bp: &mut Blueprint, report: &mut MigrationReport) -> Result<()> {
+    // this has to happen while the blueprint still reads as legacy
+    bp.wires = bp.normalized_wires();
+
+    for e in bp.entities.iter_mut() {
+        e.connections = None;
+        e.neighbours = None;
+
+        if let Some(d) = e.direction {
+            let is_diagonal_rail = e.name == "straight-rail" && d.raw() % 2 == 1;
+            if e.name == "curved-rail" || is_diagonal_rail {
+                report.note(
+                    &bp.label,
+                    Some(e),
+                    "rail geometry changed in 2.0, this piece needs relaying by hand",
+                );
+            } else if let Some(d8) = d.as_8way() {
+                e.direction = Direction::for_system(d8.to_16way(), DirectionSystem::Sixteen);
+            }
+        }
+
+        if let Some(RequestFilters::Legacy(ref mut legacy)) = e.request_filters {
+            let filters = legacy.split_off(0);
+            let section = LogisticSection {
+                index: 1,
+                filters: filters
+                    .into_iter()
+                    .map(|f| SectionFilter {
+                        index: f.index as u32,
+                        name: Some(f.name),
+                        quality: Some("normal".into()),
+                        comparator: Some(Comparator::Equal),
+                        count: Some(f.count as i32),
+                        ..SectionFilter::default()
+                    })
+                    .collect(),
+                ..LogisticSection::default()
+            };
+            e.request_filters = Some(RequestFilters::Sections(LogisticSections {
+                sections: vec![section],
+                request_from_buffers: e.request_from_buffers.take(),
+                ..LogisticSections::default()
+            }));
+        }
+
+        if let Some(ref mut cb) = e.control_behavior {
+            if let Some(filters) = cb.filters.take() {
+                let section = LogisticSection {
+                    index: 1,
+                    filters: filters
+                        .into_iter()
+                        .map(|f| SectionFilter {
+                            index: f.index as u32,
+                            signal_type: match f.signal.kind() {
+                                SignalType::Item => None,
+                                other => Some(other),
+                            },
+                            name: Some(f.signal.name),
+                            quality: Some("normal".into()),
+                            comparator: Some(Comparator::Equal),
+                            count: Some(f.count),
+                            ..SectionFilter::default()
+                        })
+                        .collect(),
+                    ..LogisticSection::default()
+                };
+                cb.sections = Some(LogisticSections {
+                    sections: vec![section],
+                    ..LogisticSections::default()
+                });
+            }
+            if let Some(ref mut decider) = cb.decider_conditions {
+                upgrade_decider(decider)?;
+            }
+            // one mode number became a switch per mode
+            if e.name.ends_with("inserter") {
+                match cb.inserter_mode() {
+                    Some(InserterMode::EnableDisable) => {
+                        cb.extra.insert("circuit_enabled".into(), Value::Bool(true));
+                    }
+                    Some(InserterMode::SetFilters) => {
+                        cb.extra
+                            .insert("circuit_set_filters".into(), Value::Bool(true));
+                    }
+                    Some(InserterMode::ReadHandContents) => {
+                        cb.circuit_read_hand_contents = Some(true)
+                    }
+                    Some(InserterMode::SetStackSize) => cb.circuit_set_stack_size = Some(true),
+                    Some(InserterMode::None) | None => (),
+                }
+                cb.circuit_mode_of_operation = None;
+            }
+            // belts and the like
+            if cb.circuit_enable_disable.take() == Some(true) {
+                cb.extra.insert("circuit_enabled".into(), Value::Bool(true));
+            }
+        }
+        let uses_colors = e
+            .control_behavior
+            .as_ref()
+            .is_some_and(|cb| cb.use_colors.is_some());
+        if uses_colors {
+            report.note(
+                &bp.label,
+                Some(e),
+                "lamp colours are set up differently in 2.0, check the colour mode",
+            );
+        }
+
+        if let Some(ItemRequest::Counts(_)) = e.items {
+            report.note(
+                &bp.label,
+                Some(e),
+                "item requests are still in the 1.x format, 2.0 needs inventory positions",
+            );
+        }
+    }
+
+    for schedule in bp.schedules.iter_mut() {
+        let legacy = match schedule.schedule {
+            ScheduleData::Legacy(ref mut records) => records.split_off(0),
+            ScheduleData::V2(_) => continue,
+        };
+        schedule.schedule = ScheduleData::V2(ScheduleWithInterrupts {
+            records: legacy,
+            ..ScheduleWithInterrupts::default()
+        });
+    }
+    Ok(())
+}
+
+// 1.x deciders have one condition and one output, 2.0 ones have a list of each
+fn upgrade_decider(d: &mut DeciderConditions) -> Result<()> {
+    let mut condition = Extra::new();
+    if let Some(s) = d.first_signal.take() {
+        condition.insert("first_signal".into(), serde_json::to_value(s)?);
+    }
+    if let Some(s) = d.second_signal.take() {
+        condition.insert("second_signal".into(), serde_json::to_value(s)?);
+    }
+    if let Some(c) = d.constant.take() {
+        condition.insert("constant".into(), c.into());
+    }
+    if let Some(c) = d.comparator.take() {
+        condition.insert("comparator".into(), serde_json::to_value(c)?);
+    }
+    let mut output = Extra::new();
+    if let Some(s) = d.output_signal.take() {
+        output.insert("signal".into(), serde_json::to_value(s)?);
+    }
+    // 2.0 copies the input count unless told not to
+    if d.copy_count_from_input.take() == Some(false) {
+        output.insert("copy_count_from_input".into(), Value::Bool(false));
+    }
+    d.extra.insert(
+        "conditions".into(),
+        Value::Array(vec![Value::Object(condition)]),
+    );
+    d.extra
+        .insert("outputs".into(), Value::Array(vec![Value::Object(output)]));
+    Ok(())
+}
+
+// Planners only have names to go on, so note any that are about to mean something broader
+fn note_merged_names<T: Serialize>(
+    item: &T,
+    label: &Option<String>,
+    from: MapVersion,
+    target: MapVersion,
+    report: &mut MigrationReport,
+) -> Result<()> {
+    if !(from < V2_0 && V2_0 <= target) {
+        return Ok(());
+    }
+    let value = serde_json::to_value(item)?;
+    for name in MERGED_2_0 {
+        if mentions(&value, name) {
+            report.note(
+                label,
+                None,
+                &format!(
+                    "{} is renamed to {} in 2.0, which also covers inserters without filters",
+                    name,
+                    renamed(name, RENAMES_2_0).unwrap_or_default()
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn mentions(v: &Value, name: &str) -> bool {
+    match v {
+        Value::Object(map) => map
+            .iter()
+            .any(|(key, val)| (key == "name" && val == name) || mentions(val, name)),
+        Value::Array(vals) => vals.iter().any(|val| mentions(val, name)),
+        _ => false,
+    }
+}
+
+// Applies every rename table released after from, up to and including target
+fn rename_prototypes<T>(item: &mut T, from: MapVersion, target: MapVersion) -> Result<()>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let tables: Vec<_> = renames()
+        .into_iter()
+        .filter(|(v, _)| from < *v && *v <= target)
+        .collect();
+    if tables.is_empty() {
+        return Ok(());
+    }
+    // renamed prototypes turn up all over the place, so go through the JSON rather than every field
+    let mut value = serde_json::to_value(&*item)?;
+    for (_, table) in tables {
+        rename_in_value(&mut value, table);
+    }
+    *item = serde_json::from_value(value)?;
+    Ok(())
+}
+
+fn renamed(name: &str, table: &[(&str, &str)]) -> Option<String> {
+    table
+        .iter()
+        .find(|(old, _)| *old == name)
+        .map(|(_, new)| new.to_string())
+}
+
+fn rename_in_value(v: &mut Value, table: &[(&str, &str)]) {
+    match v {
+        Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                match key.as_str() {
+                    // blueprint parameter names are just labels
+                    "parameters" => continue,
+                    "name" | "recipe" | "filter" => {
+                        if let Some(new) = val.as_str().and_then(|s| renamed(s, table)) {
+                            *val = Value::String(new);
+                        }
+                    }
+                    // 1.x item requests are keyed by item name
+                    "items" => {
+                        if let Value::Object(items) = val {
+                            let old = std::mem::take(items);
+                            for (item, count) in old {
+                                let item = renamed(&item, table).unwrap_or(item);
+                                // two items turning into the same one get added up
+                                let count = match (
+                                    items.get(&item).and_then(Value::as_u64),
+                                    count.as_u64(),
+                                ) {
+                                    (Some(existing), Some(n)) => Value::from(existing + n),
+                                    _ => count,
+                                };
+                                items.insert(item, count);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                rename_in_value(val, table);
+            }
+        }
+        Value::Array(vals) => {
+            for val in vals.iter_mut() {
+                rename_in_value(val, table);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_blueprint() -> Container {
+        let json = r#"{"blueprint":{"icons":[{"signal":{"type":"item","name":"stack-inserter"},"index":1}],"entities":[
+            {"entity_number":1,"name":"stack-inserter","position":{"x":0.5,"y":0.5},"direction":2},
+            {"entity_number":2,"name":"filter-inserter","position":{"x":1.5,"y":0.5},"direction":4,"filters":[{"name":"iron-plate","index":1}]},
+            {"entity_number":3,"name":"logistic-chest-requester","position":{"x":2.5,"y":0.5},"request_filters":[{"name":"iron-plate","index":1,"count":100}],"request_from_buffers":true},
+            {"entity_number":4,"name":"constant-combinator","position":{"x":3.5,"y":0.5},"control_behavior":{"filters":[{"signal":{"type":"virtual","name":"signal-A"},"count":5,"index":1}]},"connections":{"1":{"green":[{"entity_id":5}]}}},
+            {"entity_number":5,"name":"medium-electric-pole","position":{"x":4.5,"y":0.5},"connections":{"1":{"green":[{"entity_id":4}]}},"neighbours":[6]},
+            {"entity_number":6,"name":"medium-electric-pole","position":{"x":9.5,"y":0.5},"neighbours":[5]},
+            {"entity_number":7,"name":"curved-rail","position":{"x":20,"y":20},"direction":1},
+            {"entity_number":8,"name":"beacon","position":{"x":12,"y":12},"items":{"effectivity-module":2}},
+            {"entity_number":9,"name":"locomotive","position":{"x":30,"y":3},"orientation":0.25},
+            {"entity_number":10,"name":"decider-combinator","position":{"x":5.5,"y":3},"control_behavior":{"decider_conditions":{"first_signal":{"type":"item","name":"iron-plate"},"constant":100,"comparator":">","output_signal":{"type":"virtual","name":"signal-A"},"copy_count_from_input":false}}},
+            {"entity_number":11,"name":"inserter","position":{"x":6.5,"y":3.5},"control_behavior":{"circuit_mode_of_operation":4,"circuit_read_hand_contents":false}},
+            {"entity_number":12,"name":"transport-belt","position":{"x":7.5,"y":3.5},"control_behavior":{"circuit_condition":{"first_signal":{"type":"item","name":"coal"},"constant":0,"comparator":">"},"circuit_enable_disable":true}},
+            {"entity_number":13,"name":"small-lamp","position":{"x":8.5,"y":3.5},"control_behavior":{"use_colors":true}}
+        ],"schedules":[{"locomotives":[9],"schedule":[{"station":"Iron","wait_conditions":[{"type":"full","compare_type":"or"}]}]}],"item":"blueprint","label":"old","version":68722819072}}"#;
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_to_2_0() {
+        let mut c = legacy_blueprint();
+        let report = migrate(&mut c, V2_0).unwrap();
+        let bp = match c {
+            Container::Blueprint(ref bp) => bp,
+            _ => panic!("expected a blueprint"),
+        };
+        assert_eq!(bp.version, V2_0);
+        assert_eq!(bp.icons[0].signal.name, "bulk-inserter");
+        assert_eq!(bp.entities[0].name, "bulk-inserter");
+        assert_eq!(bp.entities[0].direction, Direction::new(4));
+        assert_eq!(bp.entities[1].name, "fast-inserter");
+        assert_eq!(bp.entities[1].extra["use_filters"], Value::Bool(true));
+        assert_eq!(bp.entities[2].name, "requester-chest");
+        match bp.entities[2].request_filters {
+            Some(RequestFilters::Sections(ref s)) => {
+                assert_eq!(s.request_from_buffers, Some(true));
+                assert_eq!(s.sections[0].filters[0].count, Some(100));
+            }
+            ref other => panic!("expected sections, got {:?}", other),
+        }
+        let cb = bp.entities[3].control_behavior.as_ref().unwrap();
+        assert!(cb.filters.is_none());
+        assert_eq!(
+            cb.sections.as_ref().unwrap().sections[0].filters[0].signal_type,
+            Some(SignalType::Virtual)
+        );
+        assert!(bp.entities.iter().all(|e| e.connections.is_none()));
+        assert_eq!(
+            bp.wires,
+            vec![
+                Wire::new(
+                    WireEnd::new(4, WireConnector::CircuitGreen),
+                    WireEnd::new(5, WireConnector::CircuitGreen)
+                ),
+                Wire::new(
+                    WireEnd::new(5, WireConnector::PoleCopper),
+                    WireEnd::new(6, WireConnector::PoleCopper)
+                ),
+            ]
+        );
+        assert_eq!(
+            bp.entities[7]
+                .items
+                .as_ref()
+                .unwrap()
+                .count("efficiency-module"),
+            2
+        );
+        match bp.schedules[0].schedule {
+            ScheduleData::V2(ref data) => assert_eq!(data.records.len(), 1),
+            ref other => panic!("expected a 2.0 schedule, got {:?}", other),
+        }
+
+        let decider = bp.entities[9].control_behavior.as_ref().unwrap();
+        assert_eq!(
+            serde_json::to_string(decider).unwrap(),
+            r#"{"decider_conditions":{"conditions":[{"first_signal":{"type":"item","name":"iron-plate"},"constant":100,"comparator":">"}],"outputs":[{"signal":{"type":"virtual","name":"signal-A"},"copy_count_from_input":false}]}}"#
+        );
+        let inserter = bp.entities[10].control_behavior.as_ref().unwrap();
+        assert_eq!(inserter.circuit_mode_of_operation, None);
+        assert_eq!(inserter.circuit_set_stack_size, Some(true));
+        let belt = bp.entities[11].control_behavior.as_ref().unwrap();
+        assert_eq!(belt.circuit_enable_disable, None);
+        assert_eq!(belt.extra["circuit_enabled"], Value::Bool(true));
+
+        let flagged: Vec<_> = report.issues.iter().map(|i| i.entity_number).collect();
+        assert_eq!(flagged, vec![Some(7), Some(8), Some(13)]);
+    }
+
+    #[test]
+    fn test_migrate_planner_notes_merged_names() {
+        let json = r#"{"upgrade_planner":{"settings":{"mappers":[{"from":{"type":"entity","name":"filter-inserter"},"to":{"type":"entity","name":"stack-filter-inserter"},"index":0}]},"item":"upgrade-planner","label":"filters","version":281479275675648}}"#;
+        let mut c: Container = serde_json::from_str(json).unwrap();
+        let report = migrate(&mut c, V2_0).unwrap();
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "filters: filter-inserter is renamed to fast-inserter in 2.0, which also covers inserters without filters",
+                "filters: stack-filter-inserter is renamed to bulk-inserter in 2.0, which also covers inserters without filters",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_migrate_to_1_1_only_bumps() {
+        let mut c = legacy_blueprint();
+        let report = migrate(&mut c, V1_1).unwrap();
+        assert!(report.is_clean());
+        let bp = match c {
+            Container::Blueprint(ref bp) => bp,
+            _ => panic!("expected a blueprint"),
+        };
+        assert_eq!(bp.version, V1_1);
+        assert_eq!(bp.entities[0].name, "stack-inserter");
+        assert_eq!(bp.entities[0].direction, Direction::new(2));
+        assert!(bp.wires.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_refuses_downgrade() {
+        let mut c = legacy_blueprint();
+        migrate(&mut c, V2_0).unwrap();
+        let before = c.clone();
+        let report = migrate(&mut c, V1_1).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(c, before);
+    }
+
+    #[test]
+    fn test_renamed_items_are_added_up() {
+        let mut v: Value =
+            serde_json::from_str(r#"{"items":{"speed-module":2,"speed-module-1":2,"coal":5}}"#)
+                .unwrap();
+        rename_in_value(&mut v, &[("speed-module-1", "speed-module")]);
+        assert_eq!(v["items"]["speed-module"], 4);
+        assert_eq!(v["items"]["coal"], 5);
+        assert!(v["items"].get("speed-module-1").is_none());
+    }
+}
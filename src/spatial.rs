@@ -0,0 +1,399 @@
+// Finding entities by where they are, without walking the whole entity list every time
+// Entities are bucketed into 32x32 tile chunks (the same size the game uses) by their collision box
+// and their tile footprint, so a query only looks at the chunks it touches
+
+use entities::{EntityShape, Prototypes};
+use error::Result;
+use geo::{
+    algorithm::{
+        boundingbox::BoundingBox, contains::Contains, euclidean_distance::EuclideanDistance,
+        intersects::Intersects,
+    },
+    Bbox, Point, Polygon,
+};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+use transform::{self, Flip, Replacements, Rotation, TransformIssue};
+use types::{Blueprint, Direction, DirectionSystem, Entity, Position, TileRect};
+
+pub const CHUNK_SIZE: i32 = 32;
+
+#[derive(Debug)]
+struct Indexed {
+    // what the shape was worked out from, to tell when it needs redoing
+    name: String,
+    position: Position,
+    direction: Option<Direction>,
+    orientation: Option<f64>,
+    shape: Polygon<f64>,
+    footprint: TileRect,
+    chunks: Vec<(i32, i32)>,
+}
+
+impl Indexed {
+    fn is_stale(&self, e: &Entity) -> bool {
+        self.name != e.name
+            || self.position != e.position
+            || self.direction != e.direction
+            || self.orientation != e.orientation
+    }
+}
+
+// Built from one blueprint, and kept up to date by running the transforms through it - rotate,
+// flip and replace here change bp and re-index whatever they moved. Anything else done to bp
+// directly gets picked up by sync
+#[derive(Debug)]
+pub struct SpatialIndex<'a> {
+    prototypes: &'a Prototypes,
+    system: DirectionSystem,
+    entries: HashMap<i32, Indexed>, // by entity_number
+    chunks: HashMap<(i32, i32), Vec<i32>>,
+}
+
+impl SpatialIndex<'static> {
+    pub fn new(bp: &Blueprint) -> Result<Self> {
+        SpatialIndex::with_prototypes(bp, Prototypes::vanilla())
+    }
+}
+
+impl<'a> SpatialIndex<'a> {
+    pub fn with_prototypes(bp: &Blueprint, prototypes: &'a Prototypes) -> Result<Self> {
+        let mut ret = SpatialIndex {
+            prototypes,
+            system: bp.direction_system(),
+            entries: HashMap::new(),
+            chunks: HashMap::new(),
+        };
+        for e in &bp.entities {
+            ret.insert(e)?;
+        }
+        Ok(ret)
+    }
+
+    // Bring the index back in line with bp after it's been changed, e.g. by a transform
+    // Only entities that were added, removed, moved or turned are looked at again
+    pub fn sync(&mut self, bp: &Blueprint) -> Result<()> {
+        if bp.direction_system() != self.system {
+            // every direction number means something else now
+            *self = SpatialIndex::with_prototypes(bp, self.prototypes)?;
+            return Ok(());
+        }
+        let current: HashSet<i32> = bp.entities.iter().map(|e| e.entity_number).collect();
+        let gone: Vec<i32> = self
+            .entries
+            .keys()
+            .filter(|n| !current.contains(n))
+            .cloned()
+            .collect();
+        for n in gone {
+            self.remove(n);
+        }
+        for e in &bp.entities {
+            let stale = self
+                .entries
+                .get(&e.entity_number)
+                .map_or(true, |entry| entry.is_stale(e));
+            if stale {
+                self.insert(e)?;
+            }
+        }
+        Ok(())
+    }
+
+    // transform::rotate, with the index following along
+    pub fn rotate(&mut self, bp: &mut Blueprint, rotation: Rotation) -> Result<()> {
+        transform::rotate_with(bp, rotation, self.prototypes)?;
+        self.sync(bp)
+    }
+
+    // transform::flip, with the index following along
+    pub fn flip(&mut self, bp: &mut Blueprint, flip: Flip) -> Result<Vec<TransformIssue>> {
+        let issues = transform::flip_with(bp, flip, self.prototypes)?;
+        self.sync(bp)?;
+        Ok(issues)
+    }
+
+    // transform::replace, with the index following along
+    pub fn replace(&mut self, bp: &mut Blueprint, replacements: &Replacements) -> Result<usize> {
+        let changed = transform::replace_with(bp, replacements, self.prototypes)?;
+        self.sync(bp)?;
+        Ok(changed)
+    }
+
+    // adds e, or replaces whatever had its entity_number
+    pub fn insert(&mut self, e: &Entity) -> Result<()> {
+        self.remove(e.entity_number);
+        let shape = EntityShape::with_prototypes(e, self.system, self.prototypes)?.shape;
+        let footprint = self.prototypes.footprint(e, self.system)?;
+        let bbox = shape.bbox().unwrap_or(Bbox {
+            xmin: 0.0,
+            xmax: 0.0,
+            ymin: 0.0,
+            ymax: 0.0,
+        });
+        // the footprint can reach further than the box - a curve's corners are empty
+        let mut chunks = chunks_touching(&bbox);
+        for chunk in chunks_covering(&footprint) {
+            if !chunks.contains(&chunk) {
+                chunks.push(chunk);
+            }
+        }
+        for chunk in &chunks {
+            self.chunks.entry(*chunk).or_default().push(e.entity_number);
+        }
+        self.entries.insert(
+            e.entity_number,
+            Indexed {
+                name: e.name.clone(),
+                position: e.position,
+                direction: e.direction,
+                orientation: e.orientation,
+                shape,
+                footprint,
+                chunks,
+            },
+        );
+        Ok(())
+    }
+
+    // false if it wasn't there
+    pub fn remove(&mut self, entity_number: i32) -> bool {
+        let entry = match self.entries.remove(&entity_number) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        for chunk in entry.chunks {
+            if let Some(v) = self.chunks.get_mut(&chunk) {
+                v.retain(|&n| n != entity_number);
+                if v.is_empty() {
+                    self.chunks.remove(&chunk);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Everything whose collision box reaches into rect, by entity_number
+    pub fn in_rect(&self, rect: &Bbox<f64>) -> Vec<i32> {
+        self.matching(chunks_touching(rect), |entry| entry.shape.intersects(rect))
+    }
+
+    // Everything whose collision box covers p
+    pub fn at_point(&self, p: Point<f64>) -> Vec<i32> {
+        let chunk = chunk_of(p.x(), p.y());
+        self.matching(vec![chunk], |entry| entry.shape.contains(&p))
+    }
+
+    // Everything with tile (x, y) in its footprint
+    pub fn at_tile(&self, x: i32, y: i32) -> Vec<i32> {
+        let chunk = (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE));
+        self.matching(vec![chunk], |entry| entry.footprint.contains(x, y))
+    }
+
+    // Everything whose collision box comes within radius tiles of center, nearest first
+    pub fn within_radius(&self, center: Point<f64>, radius: f64) -> Vec<i32> {
+        let rect = Bbox {
+            xmin: center.x() - radius,
+            xmax: center.x() + radius,
+            ymin: center.y() - radius,
+            ymax: center.y() + radius,
+        };
+        let mut found: Vec<(f64, i32)> = self
+            .matching(chunks_touching(&rect), |entry| {
+                entry.shape.euclidean_distance(&center) <= radius
+            })
+            .into_iter()
+            .map(|n| (self.entries[&n].shape.euclidean_distance(&center), n))
+            .collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        found.into_iter().map(|(_, n)| n).collect()
+    }
+
+    // The entity closest to p - searches outwards a ring of chunks at a time
+    pub fn nearest(&self, p: Point<f64>) -> Option<i32> {
+        let (cx, cy) = chunk_of(p.x(), p.y());
+        let furthest = self
+            .chunks
+            .keys()
+            .map(|&(x, y)| (x - cx).abs().max((y - cy).abs()))
+            .max()?;
+        let mut best: Option<(f64, i32)> = None;
+        for ring in 0..=furthest {
+            // nothing in this ring can be closer than this
+            if let Some((d, _)) = best {
+                if d <= f64::from((ring - 1) * CHUNK_SIZE) {
+                    break;
+                }
+            }
+            let on_ring = |&(x, y): &(i32, i32)| (x - cx).abs().max((y - cy).abs()) == ring;
+            // far from everything it's quicker to go through the chunks that have something in
+            let chunks: Vec<(i32, i32)> = if 8 * ring as usize >= self.chunks.len() {
+                self.chunks.keys().cloned().filter(on_ring).collect()
+            } else {
+                (cx - ring..=cx + ring)
+                    .flat_map(|x| (cy - ring..=cy + ring).map(move |y| (x, y)))
+                    .filter(on_ring)
+                    .collect()
+            };
+            for n in self.matching(chunks, |_| true) {
+                let d = self.entries[&n].shape.euclidean_distance(&p);
+                if best.map_or(true, |(best_d, best_n)| (d, n) < (best_d, best_n)) {
+                    best = Some((d, n));
+                }
+            }
+        }
+        best.map(|(_, n)| n)
+    }
+
+    // entity_numbers in any of the chunks passing test, each once, in order
+    fn matching<F: Fn(&Indexed) -> bool>(&self, chunks: Vec<(i32, i32)>, test: F) -> Vec<i32> {
+        let mut ret: Vec<i32> = chunks
+            .iter()
+            .filter_map(|c| self.chunks.get(c))
+            .flatten()
+            .cloned()
+            .collect::<HashSet<i32>>()
+            .into_iter()
+            .filter(|n| test(&self.entries[n]))
+            .collect();
+        ret.sort();
+        ret
+    }
+}
+
+fn chunk_of(x: f64, y: f64) -> (i32, i32) {
+    (
+        (x.floor() as i32).div_euclid(CHUNK_SIZE),
+        (y.floor() as i32).div_euclid(CHUNK_SIZE),
+    )
+}
+
+fn chunks_touching(b: &Bbox<f64>) -> Vec<(i32, i32)> {
+    let (x1, y1) = chunk_of(b.xmin, b.ymin);
+    let (x2, y2) = chunk_of(b.xmax, b.ymax);
+    (x1..=x2)
+        .flat_map(|x| (y1..=y2).map(move |y| (x, y)))
+        .collect()
+}
+
+fn chunks_covering(r: &TileRect) -> Vec<(i32, i32)> {
+    if r.is_empty() {
+        return Vec::new();
+    }
+    let (x1, y1) = (r.left.div_euclid(CHUNK_SIZE), r.top.div_euclid(CHUNK_SIZE));
+    let (x2, y2) = (
+        (r.right - 1).div_euclid(CHUNK_SIZE),
+        (r.bottom - 1).div_euclid(CHUNK_SIZE),
+    );
+    (x1..=x2)
+        .flat_map(|x| (y1..=y2).map(move |y| (x, y)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures;
+
+    fn blueprint() -> Blueprint {
+        fixtures::blueprint(
+            281479275675648,
+            r#""entities":[
+            {"entity_number":1,"name":"assembling-machine-1","position":{"x":1.5,"y":1.5}},
+            {"entity_number":2,"name":"inserter","position":{"x":3.5,"y":1.5},"direction":6},
+            {"entity_number":3,"name":"splitter","position":{"x":31.5,"y":32},"direction":2},
+            {"entity_number":4,"name":"transport-belt","position":{"x":-40.5,"y":-0.5}}
+        ]"#,
+        )
+    }
+
+    #[test]
+    fn test_queries() {
+        let bp = blueprint();
+        let index = SpatialIndex::new(&bp).unwrap();
+        assert_eq!(index.len(), 4);
+
+        let rect = Bbox {
+            xmin: 0.0,
+            xmax: 4.0,
+            ymin: 0.0,
+            ymax: 4.0,
+        };
+        assert_eq!(index.in_rect(&rect), vec![1, 2]);
+        assert_eq!(index.at_point(Point::new(2.6, 0.5)), vec![1]);
+        assert!(index.at_point(Point::new(2.8, 0.5)).is_empty());
+        // the splitter straddles a chunk corner
+        assert_eq!(index.at_tile(31, 31), vec![3]);
+        assert_eq!(index.at_tile(31, 32), vec![3]);
+        assert_eq!(index.within_radius(Point::new(5.0, 1.5), 2.5), vec![2, 1]);
+        assert_eq!(index.nearest(Point::new(-100.0, 0.0)), Some(4));
+        assert_eq!(index.nearest(Point::new(60.0, 60.0)), Some(3));
+    }
+
+    #[test]
+    fn test_sync_after_changes() {
+        let mut bp = blueprint();
+        let mut index = SpatialIndex::new(&bp).unwrap();
+
+        bp.entities[0].position = Position::new(101.5, 101.5);
+        bp.entities.remove(1);
+        let mut lamp = bp.entities[0].clone();
+        lamp.entity_number = 5;
+        lamp.name = "small-lamp".into();
+        lamp.position = Position::new(0.5, 0.5);
+        bp.entities.push(lamp);
+        index.sync(&bp).unwrap();
+
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.at_tile(0, 0), vec![5]);
+        assert_eq!(index.at_tile(101, 101), vec![1]);
+        assert!(index.at_tile(3, 1).is_empty());
+        assert_eq!(index.nearest(Point::new(90.0, 90.0)), Some(1));
+    }
+
+    #[test]
+    fn test_transforms_keep_it_current() {
+        let mut bp = blueprint();
+        let mut index = SpatialIndex::new(&bp).unwrap();
+
+        index.rotate(&mut bp, Rotation::Clockwise).unwrap();
+        // the assembler went from 0..3 to -3..0 across, the belt from the far left to the top
+        assert!(index.at_tile(1, 1).is_empty());
+        assert_eq!(index.at_tile(-2, 1), vec![1]);
+        assert_eq!(index.at_tile(0, -41), vec![4]);
+        assert_eq!(index.nearest(Point::new(0.5, -100.0)), Some(4));
+
+        // and back across to the right
+        assert!(index.flip(&mut bp, Flip::Horizontal).unwrap().is_empty());
+        assert_eq!(index.at_tile(1, 1), vec![1]);
+        assert!(index.at_tile(-2, 1).is_empty());
+
+        let belts = Replacements::entities(&[("transport-belt", "fast-transport-belt")]);
+        assert_eq!(index.replace(&mut bp, &belts).unwrap(), 1);
+        assert_eq!(index.at_tile(-1, -41), vec![4]);
+        assert_eq!(index.entries[&4].name, "fast-transport-belt");
+    }
+
+    #[test]
+    fn test_footprint_past_the_collision_box() {
+        // the curve's box stays in chunk 1, but its footprint starts on tile 31
+        let bp = fixtures::blueprint(
+            281479275675648,
+            r#""entities":[{"entity_number":1,"name":"curved-rail","position":{"x":33,"y":4}}]"#,
+        );
+        let index = SpatialIndex::new(&bp).unwrap();
+        assert_eq!(index.at_tile(31, 4), vec![1]);
+        assert_eq!(index.at_tile(34, 7), vec![1]);
+        assert!(index.at_tile(30, 4).is_empty());
+    }
+}
@@ -1,2 +1,816 @@
-// Here is where I'll define Blueprint transformations
-// Some ideas - rotate/mirror/replace-all
+// Here is where I define Blueprint transformations
+// Everything works on the blueprint in place, about its 0,0 - the same center the game rotates around
+// A Grid holding on to positions needs rebuilding afterwards - a SpatialIndex keeps up if they go through it
+// Mirroring can't always be done faithfully, so flip hands back a list of what it had to leave alone
+
+use entities::{Prototypes, RotationBehaviour};
+use error::{Error, Result};
+use serde_json::Value;
+use std::{collections::HashMap, fmt};
+use types::{
+    Blueprint, Direction, Direction16, DirectionSystem, Fixed, Position, SplitterPriority,
+    UpgradePlanner, UpgradeTargetType,
+};
+
+// Clockwise, the way the game's R key turns things
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    Clockwise,        // 90 degrees
+    Half,             // 180 degrees
+    CounterClockwise, // 270 degrees
+}
+
+impl Rotation {
+    // how many clockwise quarter turns this is
+    pub fn quarters(self) -> u8 {
+        match self {
+            Rotation::Clockwise => 1,
+            Rotation::Half => 2,
+            Rotation::CounterClockwise => 3,
+        }
+    }
+
+    // a point turned about 0,0
+    pub fn position(self, p: Position) -> Position {
+        match self {
+            Rotation::Clockwise => Position { x: -p.y, y: p.x },
+            Rotation::Half => Position { x: -p.x, y: -p.y },
+            Rotation::CounterClockwise => Position { x: p.y, y: -p.x },
+        }
+    }
+
+    // a 16-way heading turned the same way
+    pub fn heading(self, d: Direction16) -> Direction16 {
+        Direction16::from_u8((d as u8 + 4 * self.quarters()) % 16).unwrap_or(Direction16::North)
+    }
+}
+
+pub fn rotate(bp: &mut Blueprint, rotation: Rotation) -> Result<()> {
+    rotate_with(bp, rotation, Prototypes::vanilla())
+}
+
+// Turns the whole blueprint - positions, facings, inserter vectors, rolling stock and tiles
+pub fn rotate_with(bp: &mut Blueprint, rotation: Rotation, prototypes: &Prototypes) -> Result<()> {
+    // look everything up first so an unknown entity leaves bp as it was
+    let behaviours = bp
+        .entities
+        .iter()
+        .map(|e| {
+            prototypes
+                .get(&e.name)
+                .map(|p| p.rotation)
+                .ok_or_else(|| Error::UnknownEntity(e.name.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let system = bp.direction_system();
+
+    for (e, behaviour) in bp.entities.iter_mut().zip(behaviours) {
+        e.position = rotation.position(e.position);
+        match behaviour {
+            RotationBehaviour::Fixed => (),
+            RotationBehaviour::Orientation => {
+                if let Some(o) = e.orientation {
+                    e.orientation = Some((o + 0.25 * f64::from(rotation.quarters())) % 1.0);
+                }
+            }
+            RotationBehaviour::Cardinal
+            | RotationBehaviour::AllHeadings
+            | RotationBehaviour::CurvedRail => {
+                // no direction means north
+                let heading = e
+                    .direction
+                    .unwrap_or_default()
+                    .in_system(system)
+                    .unwrap_or(Direction16::North);
+                e.direction = match rotation.heading(heading) {
+                    // the game leaves north out
+                    Direction16::North => None,
+                    turned => Direction::for_system(turned, system),
+                };
+            }
+        }
+        // these are relative to the inserter, so they turn with it
+        if let Some(p) = e.drop_position {
+            e.drop_position = Some(rotation.position(p));
+        }
+        if let Some(p) = e.pickup_position {
+            e.pickup_position = Some(rotation.position(p));
+        }
+    }
+
+    // a tile is named by its top left corner, which after turning is a different corner
+    for t in bp.tiles.iter_mut().flatten() {
+        let turned = rotation.position(t.position);
+        let one = Fixed::from_tiles(1);
+        t.position = match rotation {
+            Rotation::Clockwise => Position {
+                x: turned.x - one,
+                y: turned.y,
+            },
+            Rotation::Half => Position {
+                x: turned.x - one,
+                y: turned.y - one,
+            },
+            Rotation::CounterClockwise => Position {
+                x: turned.x,
+                y: turned.y - one,
+            },
+        };
+    }
+
+    // the snapping grid turns too, so a 4x2 grid becomes 2x4
+    if rotation != Rotation::Half {
+        if let Some(Value::Object(grid)) = bp.extra.get_mut("snap-to-grid") {
+            if let (Some(x), Some(y)) = (grid.remove("x"), grid.remove("y")) {
+                grid.insert("x".into(), y);
+                grid.insert("y".into(), x);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Mirroring, the way the game's F and G keys do it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Flip {
+    Horizontal, // left and right swap
+    Vertical,   // top and bottom swap
+}
+
+impl Flip {
+    // a point mirrored through 0,0
+    pub fn position(self, p: Position) -> Position {
+        match self {
+            Flip::Horizontal => Position { x: -p.x, y: p.y },
+            Flip::Vertical => Position { x: p.x, y: -p.y },
+        }
+    }
+
+    // a 16-way heading mirrored the same way
+    pub fn heading(self, d: Direction16) -> Direction16 {
+        let axis = match self {
+            Flip::Horizontal => 0, // north stays north
+            Flip::Vertical => 8,   // east stays east
+        };
+        Direction16::from_u8((axis + 16 - d as u8) % 16).unwrap_or(Direction16::North)
+    }
+
+    // Curves come in pairs - 2k and 2k + 1 leave the same way but bend opposite ways - so a
+    // mirrored curve is the other half of the pair for the mirrored heading, not just the heading.
+    // count is how many directions the piece has, 8 for the 1.1 curve and 16 for 2.0's
+    fn curve(self, d: u8, count: u8) -> u8 {
+        let axis = match self {
+            Flip::Horizontal => count + 1,
+            Flip::Vertical => count + count / 2 + 1,
+        };
+        (axis - d % count) % count
+    }
+
+    // rolling stock orientation, 0-1 clockwise from north
+    fn orientation(self, o: f64) -> f64 {
+        match self {
+            Flip::Horizontal => (1.0 - o) % 1.0,
+            Flip::Vertical => (1.5 - o) % 1.0,
+        }
+    }
+}
+
+// Something a transform couldn't carry across and left for a person to look at
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformIssue {
+    pub entity_number: i32,
+    pub name: String,
+    pub reason: String,
+}
+
+impl fmt::Display for TransformIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} ({}): {}",
+            self.entity_number, self.name, self.reason
+        )
+    }
+}
+
+// Before 2.0 these can't be flipped, so their pipe connections stay where they were
+const ASYMMETRIC_FLUID_MACHINES: &[&str] = &["oil-refinery", "chemical-plant"];
+
+// 2.0's curves, which mirror pairwise like the 1.1 one does
+const CURVES_2_0: &[&str] = &[
+    "curved-rail-a",
+    "curved-rail-b",
+    "elevated-curved-rail-a",
+    "elevated-curved-rail-b",
+];
+
+// Signals and stops go on the right hand side of the track for the way trains are going
+const RIGHT_HAND_SIDE: &[&str] = &["rail-signal", "rail-chain-signal", "train-stop"];
+
+pub fn flip(bp: &mut Blueprint, flip: Flip) -> Result<Vec<TransformIssue>> {
+    flip_with(bp, flip, Prototypes::vanilla())
+}
+
+// Mirrors the whole blueprint - positions, facings, inserter vectors, splitter priorities,
+// rolling stock and tiles. Whatever the game couldn't place mirrored comes back as issues
+pub fn flip_with(
+    bp: &mut Blueprint,
+    flip: Flip,
+    prototypes: &Prototypes,
+) -> Result<Vec<TransformIssue>> {
+    // look everything up first so an unknown entity leaves bp as it was
+    let protos = bp
+        .entities
+        .iter()
+        .map(|e| {
+            prototypes
+                .get(&e.name)
+                .ok_or_else(|| Error::UnknownEntity(e.name.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let system = bp.direction_system();
+    let mut ret = Vec::new();
+
+    for (e, proto) in bp.entities.iter_mut().zip(protos) {
+        e.position = flip.position(e.position);
+        match proto.rotation {
+            RotationBehaviour::Fixed => (),
+            RotationBehaviour::Orientation => {
+                e.orientation = e.orientation.map(|o| flip.orientation(o));
+            }
+            RotationBehaviour::CurvedRail => {
+                // 2.0 keeps the 1.1 curves but counts their directions in 16ths
+                let d = e.direction.unwrap_or_default().in_system(system);
+                let d = d.unwrap_or(Direction16::North) as u8 / 2;
+                let mirrored = Direction16::from_u8(flip.curve(d, 8) * 2)
+                    .and_then(|heading| Direction::for_system(heading, system));
+                // the game leaves north out
+                e.direction = mirrored.filter(|d| d.raw() != 0);
+            }
+            RotationBehaviour::Cardinal | RotationBehaviour::AllHeadings => {
+                let d = e.direction.unwrap_or_default();
+                let mirrored = if CURVES_2_0.contains(&e.name.as_str()) {
+                    Direction::new(flip.curve(d.raw(), 16))
+                } else {
+                    let heading = d.in_system(system).unwrap_or(Direction16::North);
+                    let mut heading = flip.heading(heading);
+                    if RIGHT_HAND_SIDE.contains(&e.name.as_str()) {
+                        // mirroring put it on the left, so it has to face the other traffic
+                        heading = Rotation::Half.heading(heading);
+                    }
+                    Direction::for_system(heading, system)
+                };
+                e.direction = mirrored.filter(|d| d.raw() != 0);
+            }
+        }
+        if let Some(p) = e.drop_position {
+            e.drop_position = Some(flip.position(p));
+        }
+        if let Some(p) = e.pickup_position {
+            e.pickup_position = Some(flip.position(p));
+        }
+        // whichever side was preferred is now on the other side
+        let swap = |p: Option<SplitterPriority>| {
+            p.map(|p| match p {
+                SplitterPriority::Left => SplitterPriority::Right,
+                SplitterPriority::Right => SplitterPriority::Left,
+            })
+        };
+        e.input_priority = swap(e.input_priority);
+        e.output_priority = swap(e.output_priority);
+
+        if !proto.crafting_categories.is_empty() {
+            if system == DirectionSystem::Sixteen {
+                // 2.0 machines can be flipped, fluid boxes and all
+                e.mirror = match e.mirror {
+                    Some(true) => None,
+                    _ => Some(true),
+                };
+            } else if let Some(ref recipe) = e.recipe {
+                if ASYMMETRIC_FLUID_MACHINES.contains(&e.name.as_str()) {
+                    ret.push(TransformIssue {
+                        entity_number: e.entity_number,
+                        name: e.name.clone(),
+                        reason: format!(
+                            "{} can't be mirrored before 2.0, its pipes won't line up",
+                            recipe
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    // a tile is named by its top left corner, which after mirroring is the top right or bottom left
+    for t in bp.tiles.iter_mut().flatten() {
+        let mirrored = flip.position(t.position);
+        let one = Fixed::from_tiles(1);
+        t.position = match flip {
+            Flip::Horizontal => Position {
+                x: mirrored.x - one,
+                y: mirrored.y,
+            },
+            Flip::Vertical => Position {
+                x: mirrored.x,
+                y: mirrored.y - one,
+            },
+        };
+    }
+    Ok(ret)
+}
+
+// What to swap for what when upgrading - e.g. transport-belt to fast-transport-belt
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replacements {
+    pub entities: HashMap<String, String>, // prototype name to prototype name
+    pub items: HashMap<String, String>,    // module requests, e.g. speed-module to speed-module-2
+}
+
+impl Replacements {
+    // just entities, from (from, to) pairs
+    pub fn entities(pairs: &[(&str, &str)]) -> Self {
+        Replacements {
+            entities: pairs
+                .iter()
+                .map(|&(from, to)| (from.into(), to.into()))
+                .collect(),
+            items: HashMap::new(),
+        }
+    }
+
+    // every row of the planner with both sides filled in
+    pub fn from_upgrade_planner(up: &UpgradePlanner) -> Self {
+        let mut ret = Replacements::default();
+        for m in up.settings.iter().flat_map(|s| &s.mappers) {
+            if let (Some(from), Some(to)) = (&m.from, &m.to) {
+                let map = match from.target_type {
+                    UpgradeTargetType::Entity => &mut ret.entities,
+                    UpgradeTargetType::Item => &mut ret.items,
+                };
+                map.insert(from.name.clone(), to.name.clone());
+            }
+        }
+        ret
+    }
+}
+
+pub fn replace(bp: &mut Blueprint, replacements: &Replacements) -> Result<usize> {
+    replace_with(bp, replacements, Prototypes::vanilla())
+}
+
+// Swaps names over in place, leaving everything else about each entity - position, direction,
+// underground type, settings - as it was. Returns how many entities changed
+// Every entity mapping has to be to something the same size, otherwise nothing is touched
+pub fn replace_with(
+    bp: &mut Blueprint,
+    replacements: &Replacements,
+    prototypes: &Prototypes,
+) -> Result<usize> {
+    for (from, to) in &replacements.entities {
+        let size = |name: &String| {
+            prototypes
+                .get(name)
+                .map(|p| (p.tile_width, p.tile_height))
+                .ok_or_else(|| Error::UnknownEntity(name.clone()))
+        };
+        if size(from)? != size(to)? {
+            return Err(Error::SizeMismatch {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+
+    let mut ret = 0;
+    for e in &mut bp.entities {
+        let mut changed = false;
+        if let Some(to) = replacements.entities.get(&e.name) {
+            e.name = to.clone();
+            changed = true;
+        }
+        // each request is looked up once, so speed-module -> speed-module-2 -> speed-module-3
+        // in one planner doesn't carry a speed-module all the way through
+        if let Some(ref mut items) = e.items {
+            changed |= items.rename_all(&replacements.items);
+        }
+        if changed {
+            ret += 1;
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures;
+    use types::Container;
+
+    fn blueprint(version: u64, entities: &str, tiles: &str) -> Blueprint {
+        fixtures::blueprint(
+            version,
+            &format!(r#""entities":[{}],"tiles":[{}]"#, entities, tiles),
+        )
+    }
+
+    const ENTITIES: &str = r#"{"entity_number":1,"name":"long-handed-inserter","position":{"x":0.5,"y":-0.5},"drop_position":{"x":0.5,"y":-2.5},"pickup_position":{"x":0.5,"y":1.5}},
+        {"entity_number":2,"name":"splitter","position":{"x":2,"y":0.5},"direction":2},
+        {"entity_number":3,"name":"stone-furnace","position":{"x":-2,"y":-2}},
+        {"entity_number":4,"name":"cargo-wagon","position":{"x":5,"y":9},"orientation":0.875},
+        {"entity_number":5,"name":"straight-rail","position":{"x":-5,"y":3},"direction":3}"#;
+
+    #[test]
+    fn test_rotate_clockwise() {
+        let mut bp = blueprint(
+            281479275675648,
+            ENTITIES,
+            r#"{"name":"concrete","position":{"x":3,"y":-1}}"#,
+        );
+        rotate(&mut bp, Rotation::Clockwise).unwrap();
+        let e = &bp.entities;
+        assert_eq!(e[0].position, Position::new(0.5, 0.5));
+        assert_eq!(e[0].direction, Direction::new(2));
+        assert_eq!(e[0].drop_position, Some(Position::new(2.5, 0.5)));
+        assert_eq!(e[0].pickup_position, Some(Position::new(-1.5, 0.5)));
+        // east facing splitter ends up facing south, lying across x
+        assert_eq!(e[1].position, Position::new(-0.5, 2.0));
+        assert_eq!(e[1].direction, Direction::new(4));
+        assert_eq!(e[2].position, Position::new(2.0, -2.0));
+        assert_eq!(e[2].direction, None);
+        assert_eq!(e[3].orientation, Some(0.125));
+        assert_eq!(e[4].position, Position::new(-3.0, -5.0));
+        assert_eq!(e[4].direction, Direction::new(5));
+        // tile (3, -1) covers x 3..4, y -1..0, which turns into x 0..1, y 3..4
+        assert_eq!(
+            bp.tiles.as_ref().unwrap()[0].position,
+            Position::new(0.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn test_rotate_2_0_directions() {
+        let mut bp = blueprint(
+            562949954076672,
+            r#"{"entity_number":1,"name":"inserter","position":{"x":0.5,"y":0.5},"direction":12},
+            {"entity_number":2,"name":"straight-rail","position":{"x":1,"y":1},"direction":2}"#,
+            "",
+        );
+        rotate(&mut bp, Rotation::Clockwise).unwrap();
+        // west turns to north, which isn't written out
+        assert_eq!(bp.entities[0].direction, None);
+        assert_eq!(bp.entities[1].direction, Direction::new(6));
+    }
+
+    #[test]
+    fn test_four_quarters_is_identity() {
+        let original = blueprint(
+            281479275675648,
+            ENTITIES,
+            r#"{"name":"concrete","position":{"x":3,"y":-1}}"#,
+        );
+        let mut bp = original.clone();
+        for _ in 0..4 {
+            rotate(&mut bp, Rotation::Clockwise).unwrap();
+        }
+        assert_eq!(bp, original);
+
+        rotate(&mut bp, Rotation::Half).unwrap();
+        rotate(&mut bp, Rotation::Half).unwrap();
+        assert_eq!(bp, original);
+
+        rotate(&mut bp, Rotation::CounterClockwise).unwrap();
+        rotate(&mut bp, Rotation::Clockwise).unwrap();
+        assert_eq!(bp, original);
+    }
+
+    #[test]
+    fn test_unknown_entity_leaves_blueprint_alone() {
+        let original = blueprint(
+            281479275675648,
+            r#"{"entity_number":1,"name":"inserter","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"warp-gate","position":{"x":5,"y":5}}"#,
+            "",
+        );
+        let mut bp = original.clone();
+        assert!(rotate(&mut bp, Rotation::Clockwise).is_err());
+        assert_eq!(bp, original);
+    }
+
+    #[test]
+    fn test_rotated_balancer_still_fits() {
+        use blueprint::read_blueprint;
+        use fixtures::balancer;
+        use validate::validate_collisions;
+
+        let c = read_blueprint(&balancer()).unwrap();
+        let mut bp = c.blueprints()[0].clone();
+        let size = bp.bounding_box().unwrap();
+        rotate(&mut bp, Rotation::Clockwise).unwrap();
+        assert_eq!(validate_collisions(&bp).unwrap(), vec![]);
+        let turned = bp.bounding_box().unwrap();
+        assert_eq!(
+            (turned.width(), turned.height()),
+            (size.height(), size.width())
+        );
+    }
+
+    #[test]
+    fn test_flip_horizontal() {
+        let mut bp = blueprint(
+            281479275675648,
+            ENTITIES,
+            r#"{"name":"concrete","position":{"x":3,"y":-1}}"#,
+        );
+        bp.entities[1].input_priority = Some(SplitterPriority::Left);
+        let issues = flip(&mut bp, Flip::Horizontal).unwrap();
+        assert!(issues.is_empty());
+        let e = &bp.entities;
+        assert_eq!(e[0].position, Position::new(-0.5, -0.5));
+        assert_eq!(e[0].direction, None);
+        assert_eq!(e[0].drop_position, Some(Position::new(-0.5, -2.5)));
+        // east becomes west, and the left lane is now the right one
+        assert_eq!(e[1].position, Position::new(-2.0, 0.5));
+        assert_eq!(e[1].direction, Direction::new(6));
+        assert_eq!(e[1].input_priority, Some(SplitterPriority::Right));
+        assert_eq!(e[1].output_priority, None);
+        assert_eq!(e[3].orientation, Some(0.125));
+        assert_eq!(e[4].direction, Direction::new(5));
+        // tile x 3..4 mirrors to -4..-3
+        assert_eq!(
+            bp.tiles.as_ref().unwrap()[0].position,
+            Position::new(-4.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn test_flip_twice_is_identity() {
+        let mut original = blueprint(
+            281479275675648,
+            ENTITIES,
+            r#"{"name":"concrete","position":{"x":3,"y":-1}}"#,
+        );
+        original.entities[1].output_priority = Some(SplitterPriority::Right);
+        for &f in &[Flip::Horizontal, Flip::Vertical] {
+            let mut bp = original.clone();
+            flip(&mut bp, f).unwrap();
+            assert_ne!(bp, original);
+            flip(&mut bp, f).unwrap();
+            assert_eq!(bp, original);
+        }
+    }
+
+    #[test]
+    fn test_flip_rails_and_signals() {
+        let mut bp = blueprint(
+            281479275675648,
+            r#"{"entity_number":1,"name":"curved-rail","position":{"x":2,"y":2}},
+            {"entity_number":2,"name":"curved-rail","position":{"x":2,"y":2},"direction":3},
+            {"entity_number":3,"name":"rail-signal","position":{"x":1.5,"y":0.5}},
+            {"entity_number":4,"name":"train-stop","position":{"x":3,"y":5},"direction":2}"#,
+            "",
+        );
+        let mut vertical = bp.clone();
+        flip(&mut bp, Flip::Horizontal).unwrap();
+        let e = &bp.entities;
+        assert_eq!(e[0].direction, Direction::new(1));
+        assert_eq!(e[1].direction, Direction::new(6));
+        // east of a track going north becomes west of one going south
+        assert_eq!(e[2].position, Position::new(-1.5, 0.5));
+        assert_eq!(e[2].direction, Direction::new(4));
+        assert_eq!(e[3].direction, Direction::new(2));
+
+        flip(&mut vertical, Flip::Vertical).unwrap();
+        let e = &vertical.entities;
+        assert_eq!(e[0].direction, Direction::new(5));
+        assert_eq!(e[1].direction, Direction::new(2));
+        assert_eq!(e[2].direction, None);
+        assert_eq!(e[3].direction, Direction::new(6));
+    }
+
+    #[test]
+    fn test_flip_every_heading() {
+        for d in 0..16 {
+            let heading = Direction16::from_u8(d).unwrap();
+            assert_eq!(Flip::Horizontal.heading(heading) as u8, (16 - d) % 16);
+            assert_eq!(Flip::Vertical.heading(heading) as u8, (24 - d) % 16);
+        }
+
+        // a straight rail at every heading each direction system has
+        for &(version, count) in &[(281479275675648, 8), (562949954076672, 16)] {
+            let rails: Vec<String> = (0..count)
+                .map(|d| {
+                    format!(
+                        r#"{{"entity_number":{},"name":"straight-rail","position":{{"x":1,"y":1}},"direction":{}}}"#,
+                        d + 1,
+                        d
+                    )
+                })
+                .collect();
+            for &(f, axis) in &[(Flip::Horizontal, 0), (Flip::Vertical, count / 2)] {
+                let mut bp = blueprint(version, &rails.join(","), "");
+                assert!(flip(&mut bp, f).unwrap().is_empty());
+                for (d, e) in (0..count).zip(&bp.entities) {
+                    assert_eq!(
+                        e.direction.map_or(0, |d| d.raw()),
+                        (axis + count - d) % count,
+                        "{:?} flip of {} in {} ways",
+                        f,
+                        d,
+                        count
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_flip_2_0_curves() {
+        let original = blueprint(
+            562949954076672,
+            r#"{"entity_number":1,"name":"curved-rail-a","position":{"x":2,"y":2}},
+            {"entity_number":2,"name":"curved-rail-a","position":{"x":2,"y":2},"direction":5},
+            {"entity_number":3,"name":"elevated-curved-rail-b","position":{"x":2,"y":2},"direction":15},
+            {"entity_number":4,"name":"legacy-curved-rail","position":{"x":2,"y":2},"direction":2},
+            {"entity_number":5,"name":"legacy-curved-rail","position":{"x":2,"y":2},"direction":6}"#,
+            "",
+        );
+        let directions = |bp: &Blueprint| -> Vec<u8> {
+            bp.entities
+                .iter()
+                .map(|e| e.direction.map_or(0, |d| d.raw()))
+                .collect()
+        };
+
+        // the bend swaps sides, so north-and-left becomes north-and-right
+        let mut bp = original.clone();
+        assert!(flip(&mut bp, Flip::Horizontal).unwrap().is_empty());
+        assert_eq!(directions(&bp), vec![1, 12, 2, 0, 12]);
+        flip(&mut bp, Flip::Horizontal).unwrap();
+        assert_eq!(bp, original);
+
+        // and south-and-right for a vertical flip
+        let mut bp = original.clone();
+        assert!(flip(&mut bp, Flip::Vertical).unwrap().is_empty());
+        assert_eq!(directions(&bp), vec![9, 4, 10, 8, 4]);
+        flip(&mut bp, Flip::Vertical).unwrap();
+        assert_eq!(bp, original);
+    }
+
+    #[test]
+    fn test_flip_fluid_machines() {
+        let machines = r#"{"entity_number":1,"name":"chemical-plant","position":{"x":1.5,"y":1.5},"recipe":"plastic-bar"},
+            {"entity_number":2,"name":"assembling-machine-2","position":{"x":5.5,"y":1.5},"recipe":"iron-gear-wheel"},
+            {"entity_number":3,"name":"oil-refinery","position":{"x":10.5,"y":2.5}}"#;
+        let mut bp = blueprint(281479275675648, machines, "");
+        let issues = flip(&mut bp, Flip::Vertical).unwrap();
+        assert_eq!(
+            issues,
+            vec![TransformIssue {
+                entity_number: 1,
+                name: "chemical-plant".into(),
+                reason: "plastic-bar can't be mirrored before 2.0, its pipes won't line up".into(),
+            }]
+        );
+        assert!(bp.entities.iter().all(|e| e.mirror.is_none()));
+
+        // 2.0 just flips them
+        let mut bp = blueprint(562949954076672, machines, "");
+        assert!(flip(&mut bp, Flip::Vertical).unwrap().is_empty());
+        assert!(bp.entities.iter().all(|e| e.mirror == Some(true)));
+        flip(&mut bp, Flip::Horizontal).unwrap();
+        assert!(bp.entities.iter().all(|e| e.mirror.is_none()));
+    }
+
+    #[test]
+    fn test_flipped_balancer_still_balances() {
+        use blueprint::{read_blueprint, Grid};
+        use fixtures::balancer;
+        use std::collections::BTreeSet;
+        use types::UndergroundType;
+        use validate::validate_collisions;
+
+        // every belt feeds the same entities it did before, so the flow through it is the same
+        fn feeds(bp: &Blueprint) -> Vec<(i32, BTreeSet<i32>)> {
+            let grid = Grid::new(bp).unwrap();
+            let mut ret: Vec<(i32, BTreeSet<i32>)> = grid
+                .iter()
+                .flat_map(|(_, here)| here)
+                .filter(|e| e.underground_type != Some(UndergroundType::Input))
+                .map(|e| {
+                    let (dx, dy) = match e.direction.map(|d| d.raw()).unwrap_or(0) {
+                        0 => (0, -1),
+                        2 => (1, 0),
+                        4 => (0, 1),
+                        _ => (-1, 0),
+                    };
+                    let next = grid
+                        .footprint(e.entity_number)
+                        .unwrap()
+                        .tiles()
+                        .flat_map(|(x, y)| grid.at(x + dx, y + dy))
+                        .map(|n| n.entity_number)
+                        .filter(|&n| n != e.entity_number)
+                        .collect();
+                    (e.entity_number, next)
+                })
+                .collect();
+            ret.sort();
+            ret.dedup();
+            ret
+        }
+
+        let c = read_blueprint(&balancer()).unwrap();
+        let original = c.blueprints()[0].clone();
+        for &f in &[Flip::Horizontal, Flip::Vertical] {
+            let mut bp = original.clone();
+            assert!(flip(&mut bp, f).unwrap().is_empty());
+            assert_eq!(validate_collisions(&bp).unwrap(), vec![]);
+            assert_eq!(feeds(&bp), feeds(&original));
+        }
+    }
+
+    #[test]
+    fn test_replace_belts() {
+        let mut bp = blueprint(
+            281479275675648,
+            r#"{"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":0.5},"direction":2},
+            {"entity_number":2,"name":"underground-belt","position":{"x":1.5,"y":0.5},"direction":6,"type":"output"},
+            {"entity_number":3,"name":"splitter","position":{"x":3,"y":0.5},"input_priority":"left"},
+            {"entity_number":4,"name":"inserter","position":{"x":4.5,"y":0.5}}"#,
+            "",
+        );
+        let original = bp.clone();
+        let replacements = Replacements::entities(&[
+            ("transport-belt", "fast-transport-belt"),
+            ("underground-belt", "fast-underground-belt"),
+            ("splitter", "fast-splitter"),
+        ]);
+        assert_eq!(replace(&mut bp, &replacements).unwrap(), 3);
+        for (e, before) in bp.entities.iter().zip(&original.entities) {
+            // only the name changes
+            let mut e = e.clone();
+            e.name = before.name.clone();
+            assert_eq!(&e, before);
+        }
+        assert_eq!(bp.entities[1].name, "fast-underground-belt");
+        assert_eq!(bp.entities[3].name, "inserter");
+    }
+
+    #[test]
+    fn test_replace_from_upgrade_planner() {
+        let planner = r#"{"upgrade_planner":{"settings":{"mappers":[
+            {"from":{"type":"entity","name":"assembling-machine-1"},"to":{"type":"entity","name":"assembling-machine-2"},"index":0},
+            {"from":{"type":"item","name":"speed-module"},"to":{"type":"item","name":"speed-module-2"},"index":1},
+            {"from":{"type":"item","name":"speed-module-2"},"to":{"type":"item","name":"speed-module-3"},"index":2},
+            {"from":{"type":"entity","name":"inserter"},"index":3}
+        ]},"item":"upgrade-planner","version":281479275675648}}"#;
+        let replacements = match ::serde_json::from_str(planner).unwrap() {
+            Container::UpgradePlanner(up) => Replacements::from_upgrade_planner(&up),
+            _ => panic!("expected an upgrade planner"),
+        };
+        assert_eq!(replacements.entities.len(), 1);
+        assert_eq!(replacements.items.len(), 2);
+
+        let mut bp = blueprint(
+            281479275675648,
+            r#"{"entity_number":1,"name":"assembling-machine-1","position":{"x":1.5,"y":1.5},"items":{"speed-module":1,"speed-module-2":1}},
+            {"entity_number":2,"name":"inserter","position":{"x":3.5,"y":1.5}}"#,
+            "",
+        );
+        assert_eq!(replace(&mut bp, &replacements).unwrap(), 1);
+        assert_eq!(bp.entities[0].name, "assembling-machine-2");
+        assert_eq!(bp.entities[1].name, "inserter");
+        let items = bp.entities[0].items.as_ref().unwrap();
+        assert_eq!(
+            items.counts(),
+            vec![
+                ("speed-module-2".to_string(), 1),
+                ("speed-module-3".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_refuses_other_sizes() {
+        let original = blueprint(
+            281479275675648,
+            r#"{"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":0.5}}"#,
+            "",
+        );
+        let mut bp = original.clone();
+        let replacements = Replacements::entities(&[
+            ("transport-belt", "fast-transport-belt"),
+            ("stone-furnace", "electric-furnace"),
+        ]);
+        match replace(&mut bp, &replacements) {
+            Err(Error::SizeMismatch { from, to }) => {
+                assert_eq!(
+                    (from.as_str(), to.as_str()),
+                    ("stone-furnace", "electric-furnace")
+                )
+            }
+            other => panic!("expected a size mismatch, got {:?}", other),
+        }
+        assert_eq!(bp, original);
+    }
+}
@@ -1,150 +1,1342 @@
 // see https://wiki.factorio.com/Blueprint_string_format for specification
 
-use std::fmt;
+use entities::Prototypes;
+use error;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json;
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    fmt,
+    num::ParseIntError,
+    ops::{Add, Neg, Sub},
+    str::FromStr,
+};
+
+// Some settings go over the wire as plain integers - this gives them an enum with the same format
+macro_rules! int_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident = $val:expr,)+ }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant = $val,)+
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_u8(*self as u8)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                let n = u8::deserialize(d)?;
+                $(if n == $val {
+                    return Ok($name::$variant);
+                })+
+                Err(de::Error::custom(format!(
+                    "invalid {} {}",
+                    stringify!($name),
+                    n
+                )))
+            }
+        }
+    };
+}
 
 pub static ENTITY_LEN: usize = 8;
 
 pub type ItemCountType = u32;
+// Keys the game writes that these structs don't have a field for yet
+// Every struct flattens one of these in so newer blueprints aren't silently stripped on the way through
+pub type Extra = serde_json::Map<String, serde_json::Value>;
 pub type GraphicsVariation = u8;
 
-// Top-level container
-// I want to be able to show this - is a Display impl sufficient?
-// How do we get the full size?
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
-pub struct Container {
-    pub blueprint: Blueprint, // this should be a union of Blueprint and BlueprintBook
+// Top-level container - a blueprint string holds exactly one of these, keyed by its kind
+// e.g. {"blueprint": {...}} or {"blueprint_book": {...}}
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    Blueprint(Blueprint),
+    BlueprintBook(BlueprintBook),
+    UpgradePlanner(UpgradePlanner),
+    DeconstructionPlanner(DeconstructionPlanner),
 }
 
-// You could have a completelete separate BookContainer, and when parsing, try both?
+impl Container {
+    // the user-defined name, whatever kind of item this is
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Container::Blueprint(bp) => bp.label.as_ref(),
+            Container::BlueprintBook(book) => book.label.as_ref(),
+            Container::UpgradePlanner(up) => up.label.as_ref(),
+            Container::DeconstructionPlanner(dp) => dp.label.as_ref(),
+        }
+        .map(|s| s.as_str())
+    }
+
+    // every blueprint in here, descending into nested books
+    pub fn blueprints(&self) -> Vec<&Blueprint> {
+        match self {
+            Container::Blueprint(bp) => vec![bp],
+            Container::BlueprintBook(book) => book
+                .blueprints
+                .iter()
+                .flat_map(|entry| entry.content.blueprints())
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
 
 impl fmt::Display for Container {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.blueprint)
+        match self {
+            Container::Blueprint(bp) => write!(f, "{}", bp),
+            Container::BlueprintBook(book) => write!(f, "{}", book),
+            Container::UpgradePlanner(up) => write!(
+                f,
+                "{} (upgrade planner, {} mappings)\nmap v. {}",
+                up.label.clone().unwrap_or_else(|| "Untitled".into()),
+                up.settings.as_ref().map_or(0, |s| s.mappers.len()),
+                up.version
+            ),
+            Container::DeconstructionPlanner(dp) => write!(
+                f,
+                "{} (deconstruction planner)\nmap v. {}",
+                dp.label.clone().unwrap_or_else(|| "Untitled".into()),
+                dp.version
+            ),
+        }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct BlueprintBook {
-    pub item: String,                      // always "blueprint-book"
-    pub label: String,                     // user-defined name
-    pub blueprints: Vec<(i32, Blueprint)>, // 0-based index, blueprint from below
-    pub active_index: i32,                 // selected blueprint
-    pub version: i64,                      // map version of the map the blueprint was created in
+    #[serde(default)]
+    pub blueprints: Vec<BookEntry>, // contents of the book, may include other books
+    pub item: String, // always "blueprint-book"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // user-defined name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>, // icons of the book set by the user
+    pub active_index: u32, // selected blueprint
+    pub version: MapVersion, // map version of the map the book was created in
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+impl fmt::Display for BlueprintBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} (book, {} items):",
+            self.label.clone().unwrap_or_else(|| "Untitled".into()),
+            self.blueprints.len()
+        )?;
+        for entry in &self.blueprints {
+            writeln!(f, "[{}] {}", entry.index, entry.content)?;
+        }
+        write!(f, "map v. {}", self.version)
+    }
+}
+
+// A single slot in a book - the item itself sits alongside its index
+// e.g. {"blueprint": {...}, "index": 0}
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BookEntry {
+    #[serde(flatten)]
+    pub content: Container,
+    pub index: u32, // 0-based slot in the book
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct UpgradePlanner {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<UpgradeSettings>,
+    pub item: String, // always "upgrade-planner"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub version: MapVersion,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DeconstructionPlanner {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<DeconstructionSettings>,
+    pub item: String, // always "deconstruction-planner"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub version: MapVersion,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct UpgradeSettings {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mappers: Vec<UpgradeMapper>, // only the slots that have something set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// One row of the upgrade planner - either side can be left empty in the UI
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct UpgradeMapper {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<UpgradeTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<UpgradeTarget>,
+    pub index: u32, // 0-based row
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct UpgradeTarget {
+    #[serde(rename = "type")]
+    pub target_type: UpgradeTargetType,
+    pub name: String, // prototype name
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpgradeTargetType {
+    Entity,
+    Item, // modules
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DeconstructionSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_filter_mode: Option<FilterMode>, // whitelist when missing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entity_filters: Vec<ItemFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trees_and_rocks_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tile_filter_mode: Option<FilterMode>, // whitelist when missing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tile_filters: Vec<ItemFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tile_selection_mode: Option<TileSelectionMode>, // normal when missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+int_enum! {
+    pub enum FilterMode {
+        Whitelist = 0,
+        Blacklist = 1,
+    }
+}
+
+int_enum! {
+    pub enum TileSelectionMode {
+        Normal = 0,
+        Always = 1,
+        Never = 2,
+        Only = 3,
+    }
+}
+
+// Field order follows the game's own output so that we round-trip byte for byte
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Blueprint {
-    pub item: String,             // always "blueprint"
-    pub label: Option<String>,    // user-defined name
-    pub entities: Vec<Entity>,    // actual content
+    // 2.0 leaves out icons and entities when there aren't any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub icons: Vec<Icon>, // icons of the blueprint set by the user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<Entity>, // actual content
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tiles: Option<Vec<Tile>>, // tiles included
-    pub icons: Vec<Icon>,         // icons of the blueprint set by the user
-    pub version: i64,             // map version of the map the blueprint was created in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wires: Vec<Wire>, // 2.0 only - replaces Entity::connections and pole neighbours
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<Schedule>, // train schedules of the locomotives in here
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stock_connections: Vec<StockConnection>, // 2.0 only - which rolling stock is coupled up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<BlueprintParameter>, // 2.0 parametrised blueprints
+    pub item: String, // always "blueprint"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // user-defined name
+    pub version: MapVersion, // map version of the map the blueprint was created in
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 impl Blueprint {
-    // returns the dimension of a square which can fit all the entities
-    // I hestitate to say smallest because I'm not mathy enough and this implementation feels lazy to me
-    // I don't really care if its a size too large as long as it works.  Bite me
-    pub fn size(&self) -> usize {
-        // find the largest coord
-        let mut largest_coord: f64 = 0.0;
+    // The smallest run of whole tiles covering every entity's footprint and every tile
+    // Empty for an empty blueprint
+    pub fn bounding_box(&self) -> error::Result<TileRect> {
+        self.bounding_box_with(Prototypes::vanilla())
+    }
+
+    pub fn bounding_box_with(&self, prototypes: &Prototypes) -> error::Result<TileRect> {
+        let system = self.direction_system();
+        let mut ret = TileRect::default();
         for e in &self.entities {
-            if e.position.x.abs() > largest_coord {
-                largest_coord = e.position.x;
-            }
+            ret = ret.union(&prototypes.footprint(e, system)?);
+        }
+        for t in self.tiles.iter().flatten() {
+            let (x, y) = t.position.tile();
+            ret = ret.union(&TileRect::tile(x, y));
+        }
+        Ok(ret)
+    }
+}
+
+impl Blueprint {
+    // which layout of the JSON this blueprint uses - decided by the version it was made in
+    pub fn schema(&self) -> Schema {
+        Schema::for_version(self.version)
+    }
+
+    // how this blueprint's direction numbers should be read
+    pub fn direction_system(&self) -> DirectionSystem {
+        self.schema().direction_system()
+    }
 
-            if e.position.y.abs() > largest_coord {
-                largest_coord = e.position.y;
+    // Every wire in the blueprint in the 2.0 form, whichever schema it was written in
+    // Legacy blueprints list each wire on both ends, so those are only returned once
+    pub fn normalized_wires(&self) -> Vec<Wire> {
+        if self.schema() == Schema::V2 {
+            return self.wires.clone();
+        }
+        let mut seen = HashSet::new();
+        let mut wires = vec![];
+        for e in &self.entities {
+            for wire in e.legacy_wires() {
+                if seen.insert(wire.normalized()) {
+                    wires.push(wire);
+                }
             }
         }
-        // get the next largest integer, increment it, and double it
-        (largest_coord.ceil() as usize) * 2 + 1
+        wires
+    }
+}
+
+// The two JSON layouts the game has used - everything up to 1.1 is Legacy
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Schema {
+    Legacy,
+    V2,
+}
+
+impl Schema {
+    pub fn for_version(version: MapVersion) -> Self {
+        if version.major >= 2 {
+            Schema::V2
+        } else {
+            Schema::Legacy
+        }
+    }
+
+    pub fn direction_system(self) -> DirectionSystem {
+        match self {
+            Schema::Legacy => DirectionSystem::Eight,
+            Schema::V2 => DirectionSystem::Sixteen,
+        }
+    }
+}
+
+// One wire in a 2.0 blueprint - written as [entity, connector, entity, connector]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Wire {
+    pub from: WireEnd,
+    pub to: WireEnd,
+}
+
+impl Wire {
+    pub fn new(from: WireEnd, to: WireEnd) -> Self {
+        Wire { from, to }
+    }
+
+    // the same wire with its ends in a fixed order, for comparing wires regardless of direction
+    pub fn normalized(&self) -> Self {
+        if self.to < self.from {
+            Wire::new(self.to, self.from)
+        } else {
+            *self
+        }
+    }
+}
+
+impl Serialize for Wire {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        (
+            self.from.entity,
+            self.from.connector,
+            self.to.entity,
+            self.to.connector,
+        )
+            .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Wire {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (from_entity, from_connector, to_entity, to_connector) =
+            <(i32, WireConnector, i32, WireConnector)>::deserialize(d)?;
+        Ok(Wire::new(
+            WireEnd::new(from_entity, from_connector),
+            WireEnd::new(to_entity, to_connector),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WireEnd {
+    pub entity: i32, // entity_number
+    pub connector: WireConnector,
+}
+
+impl WireEnd {
+    pub fn new(entity: i32, connector: WireConnector) -> Self {
+        WireEnd { entity, connector }
+    }
+}
+
+int_enum! {
+    // Where on the entity a wire attaches - the combinator ones double as plain red/green for everything else
+    pub enum WireConnector {
+        CircuitRed = 1,
+        CircuitGreen = 2,
+        CombinatorOutputRed = 3,
+        CombinatorOutputGreen = 4,
+        PoleCopper = 5, // also the left side of a power switch
+        PowerSwitchRightCopper = 6,
+    }
+}
+
+impl PartialOrd for WireConnector {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WireConnector {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (*self as u8).cmp(&(*other as u8))
+    }
+}
+
+impl WireConnector {
+    // the connector for a legacy circuit_id (1 or 2) and wire colour
+    pub fn from_circuit(circuit_id: i32, red: bool) -> Self {
+        match (circuit_id, red) {
+            (2, true) => WireConnector::CombinatorOutputRed,
+            (2, false) => WireConnector::CombinatorOutputGreen,
+            (_, true) => WireConnector::CircuitRed,
+            (_, false) => WireConnector::CircuitGreen,
+        }
+    }
+
+    pub fn is_copper(self) -> bool {
+        self == WireConnector::PoleCopper || self == WireConnector::PowerSwitchRightCopper
     }
 }
 
+// 2.0 parametrised blueprint settings - a prototype or number to ask for when the blueprint is placed
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BlueprintParameter {
+    #[serde(rename = "type")]
+    pub parameter_type: BlueprintParameterType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // label shown in the dialog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>, // the parameter signal being replaced, e.g. "parameter-0"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>, // the number being replaced, as text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variable: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formula: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_parametrised: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlueprintParameterType {
+    Id,
+    Number,
+}
+
 impl fmt::Display for Blueprint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut disp_entities = String::new();
         for e in &self.entities {
             disp_entities.push_str(&format!("{}\n", e))
         }
+        let size = match self.bounding_box() {
+            Ok(b) => format!("{}x{}", b.width(), b.height()),
+            Err(_) => "unknown".into(),
+        };
         write!(
             f,
-            "{} (size: {} side square):\n{}map v. {}",
-            self.label.clone().unwrap_or("Untitled".into()),
-            self.size(),
+            "{} (size: {}):\n{}map v. {}",
+            self.label.clone().unwrap_or_else(|| "Untitled".into()),
+            size,
             disp_entities,
             self.version
         )
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-pub struct Entity {
-    pub entity_number: i32, // 1-based index of entity
-    pub name: String,       // e.g. "offshore-pump"
-    pub position: Position,
-    pub direction: Option<u32>,               //uint (optional) per spec
-    pub connections: Option<Vec<Connection>>, // circuit connection
-    //pub control_behavior:                                 // TODO what is this??
-    pub items: Option<ItemRequest>, // defines the item-request-proxy when blueprint is placed, optional
-    pub recipe: Option<String>,     // name of the recipe this machine is set to, optional
-    pub bar: Option<i32>, // index of first inaccessible item slot due to limiting with the red "bar"
-    pub infinity_settings: Option<InfinitySettings>,
-    #[serde(rename = "type")]
-    pub underground_type: Option<String>, // either "input" or "output" - type of underground belt or loader
-    pub input_priority: Option<String>, // input prio of splitter, "right" or "left" - "none" is omitted
-    pub output_priority: Option<String>, // output prio of splitter, "right" or "left" - "none" is omitted
-    pub filter: Option<String>,          // splitter filter - name of the prototype
-    pub filters: Option<Vec<ItemFilter>>, // for filter inserter or loader
-    pub override_stack_size: Option<u8>, // stack size of the inserter
-    pub drop_position: Option<Position>, // drop position of inserter
-    pub pickup_position: Option<Position>, // pickup position of inserter
-    pub request_filters: Option<LogisticFilter>,
-    pub request_from_buffers: Option<bool>, // whether chest can request from buffers
-    pub parameters: Option<SpeakerParameter>, // Programmable speaker
-    pub alert_parameters: Option<SpeakerAlertParameter>,
-    pub auto_launch: Option<bool>,            // used by rocket silo
-    pub variation: Option<GraphicsVariation>, // used by SimplyEntityWithOwner
-    pub color: Option<Color>, // SimpleEntityWithForce, SimpleEntityWithOwner, or train station
-    pub station: Option<String>, // Name of the train station
+// The game packs its version into one number, four u16s from most to least significant
+// e.g. 281479275675648 is 1.1.61.0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MapVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build: u16,
+}
+
+impl MapVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16, build: u16) -> Self {
+        MapVersion {
+            major,
+            minor,
+            patch,
+            build,
+        }
+    }
+
+    pub fn from_packed(packed: u64) -> Self {
+        MapVersion {
+            major: (packed >> 48) as u16,
+            minor: (packed >> 32) as u16,
+            patch: (packed >> 16) as u16,
+            build: packed as u16,
+        }
+    }
+
+    pub fn packed(&self) -> u64 {
+        (u64::from(self.major) << 48)
+            | (u64::from(self.minor) << 32)
+            | (u64::from(self.patch) << 16)
+            | u64::from(self.build)
+    }
+}
+
+impl From<u64> for MapVersion {
+    fn from(packed: u64) -> Self {
+        MapVersion::from_packed(packed)
+    }
+}
+
+impl From<MapVersion> for u64 {
+    fn from(v: MapVersion) -> Self {
+        v.packed()
+    }
+}
+
+// "1.1.87" - use {:#} to get the build number as well
+impl fmt::Display for MapVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if f.alternate() {
+            write!(f, ".{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+// accepts "1.1", "1.1.87" or "1.1.87.0" - anything left off is zero
+impl FromStr for MapVersion {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = [0u16; 4];
+        for (i, part) in s.trim().splitn(4, '.').enumerate() {
+            parts[i] = part.parse()?;
+        }
+        Ok(MapVersion::new(parts[0], parts[1], parts[2], parts[3]))
+    }
+}
+
+impl Serialize for MapVersion {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(self.packed())
+    }
+}
+
+impl<'de> Deserialize<'de> for MapVersion {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(MapVersion::from_packed(u64::deserialize(d)?))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entity {
+    pub entity_number: i32, // 1-based index of entity
+    pub name: String,       // e.g. "offshore-pump"
+    pub position: Position,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>, // 2.0 - omitted for normal quality
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>, // see Direction for what the number means
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub orientation: Option<f64>, // rolling stock, 0-1 clockwise from north
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_behavior: Option<ControlBehavior>, // circuit/logistic network settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections: Option<Connection>, // circuit connection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<ItemRequest>, // defines the item-request-proxy when blueprint is placed, optional
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<String>, // name of the recipe this machine is set to, optional
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe_quality: Option<String>, // 2.0 - omitted for normal quality
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<bool>, // 2.0 - machine is flipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bar: Option<i32>, // index of first inaccessible item slot due to limiting with the red "bar"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infinity_settings: Option<InfinitySettings>,
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underground_type: Option<UndergroundType>, // type of underground belt or loader
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_priority: Option<SplitterPriority>, // input prio of splitter - "none" is omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_priority: Option<SplitterPriority>, // output prio of splitter - "none" is omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>, // splitter filter - name of the prototype
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<Vec<ItemFilter>>, // for filter inserter or loader
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_stack_size: Option<u8>, // stack size of the inserter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drop_position: Option<Position>, // drop position of inserter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pickup_position: Option<Position>, // pickup position of inserter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_filters: Option<RequestFilters>, // requester/buffer chests, and 2.0 logistic sections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_from_buffers: Option<bool>, // whether chest can request from buffers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<SpeakerParameter>, // Programmable speaker
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_parameters: Option<SpeakerAlertParameter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_launch: Option<bool>, // used by rocket silo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variation: Option<GraphicsVariation>, // used by SimplyEntityWithOwner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>, // SimpleEntityWithForce, SimpleEntityWithOwner, or train station
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>, // Name of the train station
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neighbours: Option<Vec<i32>>, // legacy only - poles this one has copper wires to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<Inventory>, // cargo wagon filters and bar
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Entity {
+    // this entity's legacy connections and pole neighbours as 2.0 wires, seen from this end
+    pub fn legacy_wires(&self) -> Vec<Wire> {
+        let mut wires = vec![];
+        if let Some(ref conn) = self.connections {
+            let points = [(1, &conn.one), (2, &conn.two)];
+            for (circuit_id, point) in points.iter() {
+                if let Some(point) = point {
+                    for (red, targets) in [(true, &point.red), (false, &point.green)].iter() {
+                        let from = WireEnd::new(
+                            self.entity_number,
+                            WireConnector::from_circuit(*circuit_id, *red),
+                        );
+                        for t in targets.iter() {
+                            let to = WireEnd::new(
+                                t.entity_id,
+                                WireConnector::from_circuit(t.circuit_id.unwrap_or(1), *red),
+                            );
+                            wires.push(Wire::new(from, to));
+                        }
+                    }
+                }
+            }
+            let switch_sides = [
+                (WireConnector::PoleCopper, &conn.cu0),
+                (WireConnector::PowerSwitchRightCopper, &conn.cu1),
+            ];
+            for (connector, targets) in switch_sides.iter() {
+                for t in targets.iter().flat_map(|t| t.iter()) {
+                    let from = WireEnd::new(self.entity_number, *connector);
+                    let to = WireEnd::new(t.entity_id, WireConnector::PoleCopper);
+                    wires.push(Wire::new(from, to));
+                }
+            }
+        }
+        for n in self.neighbours.iter().flat_map(|n| n.iter()) {
+            wires.push(Wire::new(
+                WireEnd::new(self.entity_number, WireConnector::PoleCopper),
+                WireEnd::new(*n, WireConnector::PoleCopper),
+            ));
+        }
+        wires
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // TODO direction
+        write!(f, "{}", self.name)
+    }
+}
+
+// The raw direction number off the wire - 0 is always north, but the step depends on the version
+// Up to 1.1 there are 8 directions (2 is east), from 2.0 on there are 16 (4 is east)
+// Anything past 15 can't be valid in either, so it's refused while parsing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Direction(u8);
+
+impl Direction {
+    pub fn new(raw: u8) -> Option<Self> {
+        if raw < 16 {
+            Some(Direction(raw))
+        } else {
+            None
+        }
+    }
+
+    pub fn raw(self) -> u8 {
+        self.0
+    }
+
+    // None if this number doesn't exist in the 8-way system
+    pub fn as_8way(self) -> Option<Direction8> {
+        Direction8::from_u8(self.0)
+    }
+
+    pub fn as_16way(self) -> Direction16 {
+        Direction16::from_u8(self.0).unwrap_or(Direction16::North)
+    }
+
+    // read this number in whichever system the blueprint uses
+    pub fn in_system(self, system: DirectionSystem) -> Option<Direction16> {
+        match system {
+            DirectionSystem::Eight => self.as_8way().map(Direction8::to_16way),
+            DirectionSystem::Sixteen => Some(self.as_16way()),
+        }
+    }
+
+    // the number to write for this heading in the given system - None for 16-way only headings in 8-way
+    pub fn for_system(heading: Direction16, system: DirectionSystem) -> Option<Self> {
+        match system {
+            DirectionSystem::Eight => heading.to_8way().map(Direction::from),
+            DirectionSystem::Sixteen => Some(Direction::from(heading)),
+        }
+    }
+}
+
+impl From<Direction8> for Direction {
+    fn from(d: Direction8) -> Self {
+        Direction(d as u8)
+    }
+}
+
+impl From<Direction16> for Direction {
+    fn from(d: Direction16) -> Self {
+        Direction(d as u8)
+    }
+}
+
+impl Serialize for Direction {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u8(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Direction {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let n = u8::deserialize(d)?;
+        Direction::new(n).ok_or_else(|| de::Error::custom(format!("invalid direction {}", n)))
+    }
+}
+
+// Which of the two numbering schemes a blueprint's directions are in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DirectionSystem {
+    Eight,
+    Sixteen,
+}
+
+impl DirectionSystem {
+    pub fn for_version(version: MapVersion) -> Self {
+        Schema::for_version(version).direction_system()
+    }
+}
+
+int_enum! {
+    // 0.15 - 1.1
+    pub enum Direction8 {
+        North = 0,
+        NorthEast = 1,
+        East = 2,
+        SouthEast = 3,
+        South = 4,
+        SouthWest = 5,
+        West = 6,
+        NorthWest = 7,
+    }
+}
+
+impl Direction8 {
+    pub fn from_u8(n: u8) -> Option<Self> {
+        use self::Direction8::*;
+        [
+            North, NorthEast, East, SouthEast, South, SouthWest, West, NorthWest,
+        ]
+        .get(n as usize)
+        .cloned()
+    }
+
+    pub fn to_16way(self) -> Direction16 {
+        Direction16::from_u8(self as u8 * 2).unwrap_or(Direction16::North)
+    }
+}
+
+int_enum! {
+    // 2.0 onwards
+    pub enum Direction16 {
+        North = 0,
+        NorthNorthEast = 1,
+        NorthEast = 2,
+        EastNorthEast = 3,
+        East = 4,
+        EastSouthEast = 5,
+        SouthEast = 6,
+        SouthSouthEast = 7,
+        South = 8,
+        SouthSouthWest = 9,
+        SouthWest = 10,
+        WestSouthWest = 11,
+        West = 12,
+        WestNorthWest = 13,
+        NorthWest = 14,
+        NorthNorthWest = 15,
+    }
+}
+
+impl Direction16 {
+    pub fn from_u8(n: u8) -> Option<Self> {
+        use self::Direction16::*;
+        [
+            North,
+            NorthNorthEast,
+            NorthEast,
+            EastNorthEast,
+            East,
+            EastSouthEast,
+            SouthEast,
+            SouthSouthEast,
+            South,
+            SouthSouthWest,
+            SouthWest,
+            WestSouthWest,
+            West,
+            WestNorthWest,
+            NorthWest,
+            NorthNorthWest,
+        ]
+        .get(n as usize)
+        .cloned()
+    }
+
+    // None for the in-between headings 1.x can't express
+    pub fn to_8way(self) -> Option<Direction8> {
+        let n = self as u8;
+        if n % 2 == 0 {
+            Direction8::from_u8(n / 2)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndergroundType {
+    Input,
+    Output,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitterPriority {
+    Left,
+    Right,
+}
+
+// Cargo wagon inventory settings
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Inventory {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<ItemFilter>, // per-slot filters, index is 1-based
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bar: Option<u32>, // index of the first inaccessible slot
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// One piece of rolling stock and what's coupled to either end of it, by entity_number
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StockConnection {
+    pub stock: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub front: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub back: Option<i32>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// The schedule shared by one or more locomotives in the blueprint
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Schedule {
+    pub locomotives: Vec<i32>, // entity_number of each locomotive running this schedule
+    #[serde(default, skip_serializing_if = "ScheduleData::is_empty")]
+    pub schedule: ScheduleData,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Schedule {
+    // the stops, whichever schema this was written in
+    pub fn records(&self) -> &[ScheduleRecord] {
+        match self.schedule {
+            ScheduleData::Legacy(ref records) => records,
+            ScheduleData::V2(ref data) => &data.records,
+        }
+    }
+}
+
+// Up to 1.1 a schedule is just its list of stops, 2.0 wraps that up with interrupts
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ScheduleData {
+    Legacy(Vec<ScheduleRecord>),
+    V2(ScheduleWithInterrupts),
+}
+
+impl Default for ScheduleData {
+    fn default() -> Self {
+        ScheduleData::Legacy(vec![])
+    }
+}
+
+impl ScheduleData {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ScheduleData::Legacy(records) => records.is_empty(),
+            ScheduleData::V2(_) => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleWithInterrupts {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub records: Vec<ScheduleRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interrupts: Vec<ScheduleInterrupt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>, // trains sharing a named schedule
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleInterrupt {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<WaitCondition>, // when to break off the schedule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<ScheduleRecord>, // where to go instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inside_interrupt: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>, // name of the stop
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wait_conditions: Vec<WaitCondition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporary: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WaitCondition {
+    #[serde(rename = "type")]
+    pub condition_type: WaitConditionType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_type: Option<CompareType>, // how this joins the condition before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticks: Option<u32>, // time and inactivity conditions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<CircuitCondition>, // item, fluid and circuit conditions
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitConditionType {
+    Time,
+    Inactivity,
+    Full,
+    Empty,
+    ItemCount,
+    FluidCount,
+    Circuit,
+    RobotsInactive,
+    PassengerPresent,
+    PassengerNotPresent,
+    // 2.0
+    FuelItemCountAll,
+    FuelItemCountAny,
+    FuelFull,
+    DestinationFullOrNoPath,
+    SpecificDestinationFull,
+    SpecificDestinationNotFull,
+    AtStation,
+    NotAtStation,
+    DamageTaken,
+    RequestSatisfied,
+    RequestNotSatisfied,
+    AllRequestsSatisfied,
+    AnyRequestNotSatisfied,
+    AnyRequestZero,
+    AnyPlanetImportZero,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareType {
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tile {
+    pub name: String,       // prototype name of the tile (e.g. "concrete")
+    pub position: Position, // within the blueprint
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// A rectangle of whole tiles - left and top are the first tiles in, right and bottom the first ones out
+// Tile (x, y) is the square from (x, y) to (x + 1, y + 1) in blueprint coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TileRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl TileRect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        TileRect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    // just the one tile
+    pub fn tile(x: i32, y: i32) -> Self {
+        TileRect::new(x, y, x + 1, y + 1)
+    }
+
+    // the tiles touched by anything from (x1, y1) to (x2, y2)
+    pub fn covering(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        TileRect::new(
+            x1.floor() as i32,
+            y1.floor() as i32,
+            x2.ceil() as i32,
+            y2.ceil() as i32,
+        )
+    }
+
+    pub fn width(&self) -> u32 {
+        cmp::max(self.right - self.left, 0) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        cmp::max(self.bottom - self.top, 0) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    // the smallest rectangle holding both - an empty one doesn't count
+    pub fn union(&self, other: &TileRect) -> TileRect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        TileRect::new(
+            cmp::min(self.left, other.left),
+            cmp::min(self.top, other.top),
+            cmp::max(self.right, other.right),
+            cmp::max(self.bottom, other.bottom),
+        )
+    }
+
+    // where they overlap, empty if they don't
+    pub fn intersection(&self, other: &TileRect) -> TileRect {
+        let ret = TileRect::new(
+            cmp::max(self.left, other.left),
+            cmp::max(self.top, other.top),
+            cmp::min(self.right, other.right),
+            cmp::min(self.bottom, other.bottom),
+        );
+        if ret.is_empty() {
+            TileRect::default()
+        } else {
+            ret
+        }
+    }
+
+    // every tile inside, a row at a time
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (left, right) = (self.left, self.right);
+        (self.top..self.bottom).flat_map(move |y| (left..right).map(move |x| (x, y)))
+    }
+}
+
+impl fmt::Display for TileRect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{},{} .. {},{})",
+            self.left, self.top, self.right, self.bottom
+        )
+    }
+}
+
+// One coordinate, in 1/256ths of a tile - the precision the game itself keeps positions in
+// Goes over the wire as the plain number of tiles, e.g. 1.5
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const SCALE: i32 = 256; // steps per tile
+
+    pub fn from_raw(raw: i32) -> Self {
+        Fixed(raw)
+    }
+
+    // rounds to the nearest 1/256
+    pub fn from_f64(n: f64) -> Self {
+        Fixed((n * f64::from(Fixed::SCALE)).round() as i32)
+    }
+
+    pub fn from_tiles(tiles: i32) -> Self {
+        Fixed(tiles * Fixed::SCALE)
+    }
+
+    pub fn raw(self) -> i32 {
+        self.0
+    }
+
+    pub fn to_f64(self) -> f64 {
+        f64::from(self.0) / f64::from(Fixed::SCALE)
+    }
+
+    // the tile this falls in - -0.5 is in tile -1, not 0
+    pub fn tile(self) -> i32 {
+        self.0.div_euclid(Fixed::SCALE)
+    }
+
+    pub fn is_whole(self) -> bool {
+        self.0 % Fixed::SCALE == 0
+    }
+
+    // on a tile center, where odd sized entities sit
+    pub fn is_half(self) -> bool {
+        self.0.rem_euclid(Fixed::SCALE) == Fixed::SCALE / 2
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
 }
 
-impl fmt::Display for Entity {
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl From<Fixed> for f64 {
+    fn from(n: Fixed) -> f64 {
+        n.to_f64()
+    }
+}
+
+impl fmt::Display for Fixed {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // TODO direction
-        write!(f, "{}", self.name)
+        write!(f, "{}", self.to_f64())
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-pub struct Tile {
-    pub name: String,       // prototype name of the tile (e.g. "concrete")
-    pub position: Position, // within the blueprint
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_number(&self.to_f64(), s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let n = f64::deserialize(d)?;
+        let limit = f64::from(i32::MAX / Fixed::SCALE);
+        if !n.is_finite() || n.abs() > limit {
+            return Err(de::Error::custom(format!("position {} is off the map", n)));
+        }
+        Ok(Fixed::from_f64(n))
+    }
 }
 
 // 0,0 is the center
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+// Just a coordinate pair, so unlike everything else here there's no Extra
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Hash, Serialize)]
 pub struct Position {
-    pub x: f64,
-    pub y: f64,
+    pub x: Fixed,
+    pub y: Fixed,
 }
 
 impl Position {
     pub fn new(x: f64, y: f64) -> Self {
-        Position { x, y }
+        Position {
+            x: Fixed::from_f64(x),
+            y: Fixed::from_f64(y),
+        }
+    }
+
+    pub fn from_raw(x: i32, y: i32) -> Self {
+        Position {
+            x: Fixed::from_raw(x),
+            y: Fixed::from_raw(y),
+        }
+    }
+
+    // as plain numbers of tiles
+    pub fn to_f64(self) -> (f64, f64) {
+        (self.x.to_f64(), self.y.to_f64())
+    }
+
+    // the tile this point is on
+    pub fn tile(self) -> (i32, i32) {
+        (self.x.tile(), self.y.tile())
     }
+
     // returns delta_x, delta_y
     pub fn distance(&self, target: &Self) -> (f64, f64) {
-        (self.x - target.x, self.y - target.y)
+        ((self.x - target.x).to_f64(), (self.y - target.y).to_f64())
     }
-    // bp has coords with 0,0 as the center, our Grid type uses 0,0 as the top left corner
-    // translate grid returns indices in the cells vector in Grid
-    // TODO this is supes buggy
+
+    // bp has coords with 0,0 as the center, a size x size grid has 0,0 as the top left corner
+    // anything that would land left of or above the grid is clamped to 0
     pub fn grid_coords(&self, size: usize) -> (usize, usize) {
-        let shift = (size / 2) as f64;
-        ((self.x + shift) as usize, (self.y + shift) as usize)
+        let shift = (size / 2) as i32;
+        let (x, y) = self.tile();
+        (
+            cmp::max(x + shift, 0) as usize,
+            cmp::max(y + shift, 0) as usize,
+        )
+    }
+}
+
+impl Add for Position {
+    type Output = Position;
+    fn add(self, other: Position) -> Position {
+        Position {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Position {
+    type Output = Position;
+    fn sub(self, other: Position) -> Position {
+        Position {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+fn serialize_optional_number<S: Serializer>(n: &Option<f64>, s: S) -> Result<S::Ok, S::Error> {
+    match n {
+        Some(n) => serialize_number(n, s),
+        None => s.serialize_none(),
+    }
+}
+
+fn opaque() -> f64 {
+    1.0
+}
+
+// the game writes whole numbers without a trailing ".0", so we do too
+fn serialize_number<S: Serializer>(n: &f64, s: S) -> Result<S::Ok, S::Error> {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        s.serialize_i64(*n as i64)
+    } else {
+        s.serialize_f64(*n)
     }
 }
 
@@ -156,60 +1348,624 @@ impl fmt::Display for Position {
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Icon {
-    pub index: i32,       // Index of the icon, 1-based
     pub signal: SignalID, // the icon that is displayed
+    pub index: i32,       // Index of the icon, 1-based
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct SignalID {
-    pub name: String, // name of the signal prototype this signal is set to
     #[serde(rename = "type")]
-    pub signal_type: String, // either "item", "fluid", or "virtual" - you should make an enum with FromStr/ToStr
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal_type: Option<SignalType>, // 2.0 leaves this off for items, see kind()
+    pub name: String, // name of the signal prototype this signal is set to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>, // 2.0 - omitted for normal quality
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl SignalID {
+    pub fn kind(&self) -> SignalType {
+        self.signal_type.unwrap_or(SignalType::Item)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignalType {
+    Item,
+    Fluid,
+    Virtual,
+    // the rest only show up in 2.0 blueprints
+    Entity,
+    Recipe,
+    SpaceLocation,
+    AsteroidChunk,
+    Quality,
+}
+
+impl SignalType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignalType::Item => "item",
+            SignalType::Fluid => "fluid",
+            SignalType::Virtual => "virtual",
+            SignalType::Entity => "entity",
+            SignalType::Recipe => "recipe",
+            SignalType::SpaceLocation => "space-location",
+            SignalType::AsteroidChunk => "asteroid-chunk",
+            SignalType::Quality => "quality",
+        }
+    }
+}
+
+impl FromStr for SignalType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "item" => Ok(SignalType::Item),
+            "fluid" => Ok(SignalType::Fluid),
+            "virtual" => Ok(SignalType::Virtual),
+            "entity" => Ok(SignalType::Entity),
+            "recipe" => Ok(SignalType::Recipe),
+            "space-location" => Ok(SignalType::SpaceLocation),
+            "asteroid-chunk" => Ok(SignalType::AsteroidChunk),
+            "quality" => Ok(SignalType::Quality),
+            _ => Err(format!("unknown signal type {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for SignalType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 // the spec has digits 1 and 2 as key names
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Connection {
     #[serde(rename = "1")]
-    pub one: ConnectionPoint, // Default for everything that doens't have multiple connection points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub one: Option<ConnectionPoint>, // Default for everything that doens't have multiple connection points
     #[serde(rename = "2")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub two: Option<ConnectionPoint>, // e.g. the "output" of an arithmetic combinator
+    #[serde(rename = "Cu0")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cu0: Option<Vec<CopperConnection>>, // power switch, left side
+    #[serde(rename = "Cu1")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cu1: Option<Vec<CopperConnection>>, // power switch, right side
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CopperConnection {
+    pub entity_id: i32,
+    pub wire_id: i32, // always 0 - the pole end
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ConnectionPoint {
-    pub red: Vec<ConnectionData>,   // all red wire connections
+    // the game leaves out a colour entirely when nothing is wired with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub red: Vec<ConnectionData>, // all red wire connections
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub green: Vec<ConnectionData>, // all green wire connections
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ConnectionData {
     pub entity_id: i32,
-    pub circuit_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_id: Option<i32>, // omitted when the target only has one connection point
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// All the circuit and logistic network settings an entity can carry
+// Like Entity itself this is one flat struct - which keys show up depends on the kind of entity
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ControlBehavior {
+    // combinators
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arithmetic_conditions: Option<ArithmeticConditions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decider_conditions: Option<DeciderConditions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<Vec<ConstantCombinatorParameters>>, // constant combinator outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_on: Option<bool>, // constant combinator on/off switch, omitted when on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sections: Option<LogisticSections>, // 2.0 constant combinator outputs
+    // anything that can be enabled or disabled by a condition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_condition: Option<CircuitCondition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_to_logistic_network: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logistic_condition: Option<CircuitCondition>,
+    // inserters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_mode_of_operation: Option<u8>, // meaning depends on the entity, see inserter_mode()
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_read_hand_contents: Option<bool>, // belts use this one too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_hand_read_mode: Option<ReadMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_set_stack_size: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_control_input_signal: Option<SignalID>,
+    // transport belts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_enable_disable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_contents_read_mode: Option<ReadMode>,
+    // lamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_colors: Option<bool>, // take the lamp color from color signals
+    // train stops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_to_train: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_from_train: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_stopped_train: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub train_stopped_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_trains_limit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trains_limit_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_trains_count: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trains_count_signal: Option<SignalID>,
+    // rail and chain signals
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_close_signal: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_read_signal: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub red_output_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orange_output_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub green_output_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blue_output_signal: Option<SignalID>, // chain signals only
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl ControlBehavior {
+    // circuit_mode_of_operation is shared between entity kinds, so it's only read as an inserter mode on request
+    pub fn inserter_mode(&self) -> Option<InserterMode> {
+        self.circuit_mode_of_operation
+            .and_then(InserterMode::from_u8)
+    }
+}
+
+int_enum! {
+    pub enum InserterMode {
+        EnableDisable = 0,
+        SetFilters = 1,
+        ReadHandContents = 2,
+        None = 3,
+        SetStackSize = 4,
+    }
+}
+
+impl InserterMode {
+    pub fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(InserterMode::EnableDisable),
+            1 => Some(InserterMode::SetFilters),
+            2 => Some(InserterMode::ReadHandContents),
+            3 => Some(InserterMode::None),
+            4 => Some(InserterMode::SetStackSize),
+            _ => None,
+        }
+    }
+}
+
+int_enum! {
+    // how hand or belt contents get put on the wire
+    pub enum ReadMode {
+        Pulse = 0,
+        Hold = 1,
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CircuitCondition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second_signal: Option<SignalID>, // either this or constant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constant: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>, // the game assumes "<" when this is missing
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DeciderConditions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second_signal: Option<SignalID>, // either this or constant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constant: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_signal: Option<SignalID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_count_from_input: Option<bool>, // false means output 1
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ArithmeticConditions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_signal: Option<SignalID>, // either this or first_constant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_constant: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second_signal: Option<SignalID>, // either this or second_constant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub second_constant: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<ArithmeticOperation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_signal: Option<SignalID>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ConstantCombinatorParameters {
+    pub signal: SignalID,
+    pub count: i32,
+    pub index: i32, // 1-based slot in the combinator
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum Comparator {
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = "=")]
+    Equal,
+    #[serde(rename = "\u{2265}")]
+    GreaterOrEqual,
+    #[serde(rename = "\u{2264}")]
+    LessOrEqual,
+    #[serde(rename = "\u{2260}")]
+    NotEqual,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum ArithmeticOperation {
+    #[serde(rename = "*")]
+    Multiply,
+    #[serde(rename = "/")]
+    Divide,
+    #[serde(rename = "+")]
+    Add,
+    #[serde(rename = "-")]
+    Subtract,
+    #[serde(rename = "%")]
+    Modulo,
+    #[serde(rename = "^")]
+    Power,
+    #[serde(rename = "<<")]
+    LeftShift,
+    #[serde(rename = ">>")]
+    RightShift,
+    #[serde(rename = "AND")]
+    And,
+    #[serde(rename = "OR")]
+    Or,
+    #[serde(rename = "XOR")]
+    Xor,
+}
+
+// Module and fuel requests - there are two formats depending on which version wrote the blueprint
+// 0.16/1.x: {"speed-module": 2} - item name to amount requested
+// 2.0: [{"id": {"name": "speed-module", "quality": "rare"}, "items": {"in_inventory": [...]}}]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ItemRequest {
+    Counts(ItemCounts),
+    InsertPlans(Vec<InsertPlan>),
+}
+
+impl ItemRequest {
+    // how many of this item are requested in total, across every quality and slot
+    pub fn count(&self, name: &str) -> ItemCountType {
+        self.counts()
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, c)| c)
+            .sum()
+    }
+
+    // item name to amount, whichever format this is - a name shows up once per quality in 2.0
+    pub fn counts(&self) -> Vec<(String, ItemCountType)> {
+        match self {
+            ItemRequest::Counts(counts) => counts.0.clone(),
+            ItemRequest::InsertPlans(plans) => plans
+                .iter()
+                .map(|p| (p.id.name.clone(), p.items.total()))
+                .collect(),
+        }
+    }
+
+    // swap one item for another in place, keeping amounts and slots - e.g. upgrading modules
+    // in the 1.x format two requests that end up with the same name are combined
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let mut renames = HashMap::new();
+        renames.insert(from.to_string(), to.to_string());
+        self.rename_all(&renames);
+    }
+
+    // rename several at once, each item looked up once - with a -> b and b -> c an a ends up a b,
+    // not a c. false if nothing was renamed
+    pub fn rename_all(&mut self, renames: &HashMap<String, String>) -> bool {
+        let mut changed = false;
+        match self {
+            ItemRequest::Counts(counts) => {
+                let mut renamed = ItemCounts::default();
+                for (name, count) in counts.0.drain(..) {
+                    let name = match renames.get(&name) {
+                        Some(to) => {
+                            changed = true;
+                            to.clone()
+                        }
+                        None => name,
+                    };
+                    let existing = renamed.get(&name).unwrap_or(0);
+                    renamed.insert(&name, existing + count);
+                }
+                *counts = renamed;
+            }
+            ItemRequest::InsertPlans(plans) => {
+                for plan in plans.iter_mut() {
+                    if let Some(to) = renames.get(&plan.id.name) {
+                        plan.id.name = to.clone();
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ItemRequest::Counts(counts) => counts.0.is_empty(),
+            ItemRequest::InsertPlans(plans) => plans.is_empty(),
+        }
+    }
+}
+
+// An ordered item name -> count map, kept in the order the game wrote it so we round-trip exactly
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ItemCounts(pub Vec<(String, ItemCountType)>);
+
+impl ItemCounts {
+    pub fn get(&self, name: &str) -> Option<ItemCountType> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, c)| *c)
+    }
+
+    // replaces the amount if the item is already requested, otherwise adds it at the end
+    pub fn insert(&mut self, name: &str, count: ItemCountType) {
+        match self.0.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = count,
+            None => self.0.push((name.into(), count)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ItemCountType> {
+        let idx = self.0.iter().position(|(n, _)| n == name)?;
+        Some(self.0.remove(idx).1)
+    }
+}
+
+impl Serialize for ItemCounts {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = s.serialize_map(Some(self.0.len()))?;
+        for (name, count) in &self.0 {
+            map.serialize_entry(name, count)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemCounts {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct ItemCountsVisitor;
+
+        impl<'de> de::Visitor<'de> for ItemCountsVisitor {
+            type Value = ItemCounts;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of item names to counts")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(
+                self,
+                mut access: A,
+            ) -> Result<ItemCounts, A::Error> {
+                let mut counts = Vec::new();
+                while let Some((name, count)) = access.next_entry()? {
+                    counts.push((name, count));
+                }
+                Ok(ItemCounts(counts))
+            }
+        }
+
+        d.deserialize_map(ItemCountsVisitor)
+    }
+}
+
+// 2.0 insert plan - which item goes into which inventory slots of the entity
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InsertPlan {
+    pub id: ItemIdAndQuality,
+    pub items: ItemInventoryPositions,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ItemIdAndQuality {
+    pub name: String, // item prototype
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>, // omitted for normal quality
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ItemInventoryPositions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub in_inventory: Vec<InventoryPosition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid_count: Option<ItemCountType>, // items going into an equipment grid
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl ItemInventoryPositions {
+    pub fn total(&self) -> ItemCountType {
+        let stacks: ItemCountType = self.in_inventory.iter().map(|p| p.count.unwrap_or(1)).sum();
+        stacks + self.grid_count.unwrap_or(0)
+    }
 }
 
-// One or more key-val pairs - key is String of iitem name, value is amt requested
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-pub struct ItemRequest {}
+pub struct InventoryPosition {
+    pub inventory: u32, // inventory index as defined by the entity, e.g. 4 for assembler modules
+    pub stack: u32,     // 0-based slot within that inventory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<ItemCountType>, // omitted when it's just one
+    #[serde(flatten)]
+    pub extra: Extra,
+}
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ItemFilter {
     pub name: String, // name of prototype
     pub index: i32,   // index of filter, 1-based
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct InfinitySettings {
     pub remove_unfiltered_items: bool, // this is a checkbox in the UI
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub filters: Option<Vec<InfinityFilter>>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct InfinityFilter {
     pub name: String, // name of the prototype
     pub count: ItemCountType,
-    pub mode: String, // either "at-least", "at-most", "exactly" - TODO enum
-    pub index: i32,   // 1-based
+    pub mode: InfinityFilterMode,
+    pub index: i32, // 1-based
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InfinityFilterMode {
+    AtLeast,
+    AtMost,
+    Exactly,
+}
+
+// Requests on a logistic chest - a plain list up to 1.1, grouped into sections from 2.0
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum RequestFilters {
+    Legacy(Vec<LogisticFilter>),
+    Sections(LogisticSections),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct LogisticSections {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<LogisticSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trash_not_requested: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_from_buffers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct LogisticSection {
+    pub index: u32, // 1-based
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<SectionFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>, // named logistic group shared across the map
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub multiplier: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>, // omitted when active
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SectionFilter {
+    pub index: u32, // 1-based
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal_type: Option<SignalType>, // omitted for items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<Comparator>, // applies to quality
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u32>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -217,6 +1973,8 @@ pub struct LogisticFilter {
     pub name: String,         // name ofthe prototype
     pub index: i32,           // 1-based
     pub count: ItemCountType, // number filter is set to, 0 for storage chests
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -224,6 +1982,8 @@ pub struct SpeakerParameter {
     pub playback_volume: f64,    // volume of speaker
     pub playback_globally: bool, // global playback enabled
     pub allow_polyphony: bool,   // speaks for itself
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -232,14 +1992,23 @@ pub struct SpeakerAlertParameter {
     pub show_on_map: bool,        // is icon shown on map
     pub icon_signal_id: SignalID, //what to display with alert
     pub alert_message: String,    // body of the alert
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Color {
-    pub r: i32,
-    pub g: i32,
-    pub b: i32,
-    pub a: i32,
+    // each channel is 0-1, the game leaves out alpha sometimes
+    #[serde(serialize_with = "serialize_number")]
+    pub r: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub g: f64,
+    #[serde(serialize_with = "serialize_number")]
+    pub b: f64,
+    #[serde(default = "opaque", serialize_with = "serialize_number")]
+    pub a: f64,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[cfg(test)]
@@ -280,4 +2049,399 @@ mod tests {
         let (target_x, target_y) = (1, 3);
         assert_eq!(pos.grid_coords(size), (target_x, target_y))
     }
+    #[test]
+    fn test_unknown_fields_survive_roundtrip() {
+        use super::Container;
+        use serde_json::{self, Value};
+
+        let json = r#"{"blueprint":{"description":"from the future","snap-to-grid":{"x":4,"y":4},"icons":[{"signal":{"type":"item","name":"lamp","quality":"rare"},"index":1}],"entities":[{"entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5},"control_behavior":{"use_colors":true,"circuit_condition":{"first_signal":{"type":"virtual","name":"signal-A"},"constant":0,"comparator":">"}},"always_show":true}],"item":"blueprint","version":281479275675648}}"#;
+        let mut c: Container = serde_json::from_str(json).unwrap();
+        if let Container::Blueprint(ref mut bp) = c {
+            assert_eq!(bp.extra["description"], "from the future");
+            assert!(bp.entities[0].extra.contains_key("always_show"));
+            bp.label = Some("edited".into());
+        }
+        let mut target: Value = serde_json::from_str(json).unwrap();
+        target["blueprint"]["label"] = "edited".into();
+        assert_eq!(serde_json::to_value(&c).unwrap(), target)
+    }
+    #[test]
+    fn test_control_behavior_roundtrip() {
+        use super::{ArithmeticOperation, Comparator, Container, InserterMode, ReadMode};
+        use serde_json::{self, Value};
+
+        let json = r#"{"blueprint":{"icons":[{"signal":{"type":"item","name":"decider-combinator"},"index":1}],"entities":[
+            {"entity_number":1,"name":"decider-combinator","position":{"x":0,"y":0.5},"direction":4,"control_behavior":{"decider_conditions":{"first_signal":{"type":"virtual","name":"signal-each"},"constant":10,"comparator":"≥","output_signal":{"type":"virtual","name":"signal-each"},"copy_count_from_input":true}},"connections":{"1":{"red":[{"entity_id":2,"circuit_id":2}]},"2":{"green":[{"entity_id":3}]}}},
+            {"entity_number":2,"name":"arithmetic-combinator","position":{"x":1,"y":0.5},"control_behavior":{"arithmetic_conditions":{"first_signal":{"type":"item","name":"iron-plate"},"second_constant":2,"operation":"<<","output_signal":{"type":"virtual","name":"signal-A"}}}},
+            {"entity_number":3,"name":"constant-combinator","position":{"x":2,"y":0},"control_behavior":{"filters":[{"signal":{"type":"fluid","name":"water"},"count":-5,"index":1}],"is_on":false}},
+            {"entity_number":4,"name":"small-lamp","position":{"x":3,"y":0},"control_behavior":{"circuit_condition":{"first_signal":{"type":"virtual","name":"signal-A"},"constant":0,"comparator":">"},"use_colors":true}},
+            {"entity_number":5,"name":"fast-inserter","position":{"x":4,"y":0},"control_behavior":{"circuit_mode_of_operation":2,"circuit_read_hand_contents":true,"circuit_hand_read_mode":1}},
+            {"entity_number":6,"name":"transport-belt","position":{"x":5,"y":0},"control_behavior":{"circuit_enable_disable":false,"circuit_read_hand_contents":true,"circuit_contents_read_mode":0}},
+            {"entity_number":7,"name":"train-stop","position":{"x":7,"y":1},"control_behavior":{"send_to_train":false,"read_from_train":true,"read_stopped_train":true,"train_stopped_signal":{"type":"virtual","name":"signal-T"},"set_trains_limit":true,"trains_limit_signal":{"type":"virtual","name":"signal-L"}},"color":{"r":1,"g":0.5,"b":0,"a":0.5},"station":"Iron"},
+            {"entity_number":8,"name":"rail-chain-signal","position":{"x":9.5,"y":0.5},"control_behavior":{"circuit_read_signal":true,"red_output_signal":{"type":"virtual","name":"signal-red"},"orange_output_signal":{"type":"virtual","name":"signal-yellow"},"green_output_signal":{"type":"virtual","name":"signal-green"},"blue_output_signal":{"type":"virtual","name":"signal-blue"}}}
+        ],"item":"blueprint","version":73014444032}}"#;
+        let c: Container = serde_json::from_str(json).unwrap();
+        let bp = match c {
+            Container::Blueprint(ref bp) => bp,
+            _ => panic!("expected a blueprint"),
+        };
+        for e in &bp.entities {
+            let cb = e.control_behavior.as_ref().unwrap();
+            assert!(cb.extra.is_empty(), "{} has unmodelled keys", e.name);
+        }
+        let decider = bp.entities[0].control_behavior.as_ref().unwrap();
+        assert_eq!(
+            decider.decider_conditions.as_ref().unwrap().comparator,
+            Some(Comparator::GreaterOrEqual)
+        );
+        let arith = bp.entities[1].control_behavior.as_ref().unwrap();
+        assert_eq!(
+            arith.arithmetic_conditions.as_ref().unwrap().operation,
+            Some(ArithmeticOperation::LeftShift)
+        );
+        let inserter = bp.entities[4].control_behavior.as_ref().unwrap();
+        assert_eq!(
+            inserter.inserter_mode(),
+            Some(InserterMode::ReadHandContents)
+        );
+        assert_eq!(inserter.circuit_hand_read_mode, Some(ReadMode::Hold));
+
+        let target: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_value(&c).unwrap(), target)
+    }
+    #[test]
+    fn test_bad_read_mode_rejected() {
+        use super::ControlBehavior;
+        use serde_json;
+
+        assert!(
+            serde_json::from_str::<ControlBehavior>(r#"{"circuit_contents_read_mode":7}"#).is_err()
+        );
+    }
+    #[test]
+    fn test_item_request_counts_roundtrip() {
+        use super::ItemRequest;
+        use serde_json;
+        use std::collections::HashMap;
+
+        let json = r#"{"speed-module-3":2,"productivity-module":1}"#;
+        let mut items: ItemRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(items.count("speed-module-3"), 2);
+        assert_eq!(serde_json::to_string(&items).unwrap(), json);
+
+        items.rename("productivity-module", "speed-module-3");
+        assert_eq!(items.count("speed-module-3"), 3);
+        assert_eq!(items.count("productivity-module"), 0);
+        assert_eq!(
+            serde_json::to_string(&items).unwrap(),
+            r#"{"speed-module-3":3}"#
+        );
+
+        // no chaining, and a renamed item keeps its place
+        let mut items: ItemRequest =
+            serde_json::from_str(r#"{"speed-module":1,"speed-module-2":2,"coal":5}"#).unwrap();
+        let renames = [
+            ("speed-module", "speed-module-2"),
+            ("speed-module-2", "speed-module-3"),
+        ]
+        .iter()
+        .map(|&(from, to)| (from.to_string(), to.to_string()))
+        .collect();
+        assert!(items.rename_all(&renames));
+        assert_eq!(
+            serde_json::to_string(&items).unwrap(),
+            r#"{"speed-module-2":1,"speed-module-3":2,"coal":5}"#
+        );
+        assert!(!items.rename_all(&HashMap::new()));
+    }
+    #[test]
+    fn test_item_request_insert_plan_roundtrip() {
+        use super::ItemRequest;
+        use serde_json;
+
+        let json = r#"[{"id":{"name":"productivity-module-3","quality":"legendary"},"items":{"in_inventory":[{"inventory":4,"stack":0},{"inventory":4,"stack":1}]}},{"id":{"name":"coal"},"items":{"in_inventory":[{"inventory":1,"stack":0,"count":50}]}}]"#;
+        let mut items: ItemRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(items.count("productivity-module-3"), 2);
+        assert_eq!(items.count("coal"), 50);
+        assert_eq!(serde_json::to_string(&items).unwrap(), json);
+
+        items.rename("coal", "solid-fuel");
+        assert_eq!(items.count("solid-fuel"), 50);
+        if let ItemRequest::InsertPlans(ref plans) = items {
+            assert_eq!(plans[1].items.in_inventory[0].count, Some(50));
+        }
+    }
+    #[test]
+    fn test_map_version_unpack() {
+        use super::MapVersion;
+
+        let v = MapVersion::from(281479275675648);
+        assert_eq!(v, MapVersion::new(1, 1, 61, 0));
+        assert_eq!(v.packed(), 281479275675648);
+        assert_eq!(format!("{}", v), "1.1.61");
+        assert_eq!(
+            format!("{:#}", MapVersion::from(562949954076673)),
+            "2.0.10.1"
+        );
+    }
+    #[test]
+    fn test_map_version_ordering() {
+        use super::MapVersion;
+
+        let old = MapVersion::from(68722819072); // 0.16.51
+        let new: MapVersion = "1.1.87".parse().unwrap();
+        assert!(old < new);
+        assert!(new < "2.0".parse().unwrap());
+        assert!("1.x".parse::<MapVersion>().is_err());
+    }
+    #[test]
+    fn test_direction_systems() {
+        use super::{Direction, Direction16, Direction8, DirectionSystem};
+        use serde_json;
+
+        let east: Direction = serde_json::from_str("2").unwrap();
+        assert_eq!(east.as_8way(), Some(Direction8::East));
+        assert_eq!(
+            east.in_system(DirectionSystem::Eight),
+            Some(Direction16::East)
+        );
+        assert_eq!(
+            east.in_system(DirectionSystem::Sixteen),
+            Some(Direction16::NorthEast)
+        );
+        assert_eq!(
+            Direction::for_system(Direction16::East, DirectionSystem::Sixteen),
+            Some(Direction::new(4).unwrap())
+        );
+        assert_eq!(
+            Direction::for_system(Direction16::NorthNorthEast, DirectionSystem::Eight),
+            None
+        );
+        assert!(serde_json::from_str::<Direction>("16").is_err());
+    }
+    #[test]
+    fn test_string_enums() {
+        use super::{InfinityFilterMode, SignalID, SignalType, SplitterPriority, UndergroundType};
+        use serde_json;
+
+        let signal: SignalID =
+            serde_json::from_str(r#"{"type":"virtual","name":"signal-A"}"#).unwrap();
+        assert_eq!(signal.signal_type, Some(SignalType::Virtual));
+        assert_eq!("space-location".parse(), Ok(SignalType::SpaceLocation));
+        assert!(serde_json::from_str::<SignalID>(r#"{"type":"potato","name":"x"}"#).is_err());
+        assert_eq!(
+            serde_json::from_str::<InfinityFilterMode>(r#""at-least""#).unwrap(),
+            InfinityFilterMode::AtLeast
+        );
+        assert_eq!(
+            serde_json::to_string(&UndergroundType::Output).unwrap(),
+            r#""output""#
+        );
+        assert!(serde_json::from_str::<SplitterPriority>(r#""none""#).is_err());
+    }
+    #[test]
+    fn test_planners_roundtrip() {
+        use super::{Container, FilterMode, TileSelectionMode, UpgradeTargetType};
+        use serde_json;
+
+        let upgrade = r#"{"upgrade_planner":{"settings":{"mappers":[{"from":{"type":"entity","name":"transport-belt"},"to":{"type":"entity","name":"fast-transport-belt"},"index":0},{"from":{"type":"item","name":"speed-module"},"index":3}],"description":"yellow to red"},"item":"upgrade-planner","label":"belts","version":281479275675648}}"#;
+        let c: Container = serde_json::from_str(upgrade).unwrap();
+        match c {
+            Container::UpgradePlanner(ref up) => {
+                let mappers = &up.settings.as_ref().unwrap().mappers;
+                assert_eq!(mappers.len(), 2);
+                assert_eq!(mappers[0].to.as_ref().unwrap().name, "fast-transport-belt");
+                assert_eq!(
+                    mappers[1].from.as_ref().unwrap().target_type,
+                    UpgradeTargetType::Item
+                );
+                assert!(mappers[1].to.is_none());
+            }
+            _ => panic!("expected an upgrade planner"),
+        }
+        assert_eq!(serde_json::to_string(&c).unwrap(), upgrade);
+
+        let decon = r#"{"deconstruction_planner":{"settings":{"entity_filter_mode":1,"entity_filters":[{"name":"stone-furnace","index":1}],"trees_and_rocks_only":true,"tile_filters":[{"name":"concrete","index":1}],"tile_selection_mode":3},"item":"deconstruction-planner","version":281479275675648}}"#;
+        let c: Container = serde_json::from_str(decon).unwrap();
+        match c {
+            Container::DeconstructionPlanner(ref dp) => {
+                let settings = dp.settings.as_ref().unwrap();
+                assert_eq!(settings.entity_filter_mode, Some(FilterMode::Blacklist));
+                assert_eq!(settings.tile_selection_mode, Some(TileSelectionMode::Only));
+                assert_eq!(settings.trees_and_rocks_only, Some(true));
+            }
+            _ => panic!("expected a deconstruction planner"),
+        }
+        assert_eq!(serde_json::to_string(&c).unwrap(), decon);
+    }
+    #[test]
+    fn test_train_blueprint_roundtrip() {
+        use super::{CompareType, Container, WaitConditionType};
+        use serde_json;
+
+        let json = r#"{"blueprint":{"icons":[{"signal":{"type":"item","name":"locomotive"},"index":1}],"entities":[{"entity_number":1,"name":"locomotive","position":{"x":-3,"y":1},"orientation":0.75,"items":{"coal":50}},{"entity_number":2,"name":"cargo-wagon","position":{"x":4,"y":1},"orientation":0.25,"inventory":{"filters":[{"name":"iron-ore","index":1},{"name":"iron-ore","index":2}],"bar":20}},{"entity_number":3,"name":"train-stop","position":{"x":1,"y":-1},"direction":6,"color":{"r":0.5,"g":0,"b":1,"a":0.5},"station":"Iron Drop"}],"schedules":[{"locomotives":[1],"schedule":[{"station":"Iron Pickup","wait_conditions":[{"type":"full","compare_type":"or"},{"type":"inactivity","compare_type":"or","ticks":300}]},{"station":"Iron Drop","wait_conditions":[{"type":"item_count","compare_type":"and","condition":{"first_signal":{"type":"item","name":"iron-ore"},"constant":0,"comparator":"="}}]}]}],"item":"blueprint","version":281479275675648}}"#;
+        let c: Container = serde_json::from_str(json).unwrap();
+        match c {
+            Container::Blueprint(ref bp) => {
+                assert_eq!(bp.entities[0].orientation, Some(0.75));
+                assert_eq!(bp.entities[1].inventory.as_ref().unwrap().filters.len(), 2);
+                let schedule = &bp.schedules[0];
+                assert_eq!(schedule.locomotives, vec![1]);
+                let wait = &schedule.records()[1].wait_conditions[0];
+                assert_eq!(wait.condition_type, WaitConditionType::ItemCount);
+                assert_eq!(wait.compare_type, Some(CompareType::And));
+            }
+            _ => panic!("expected a blueprint"),
+        }
+        assert_eq!(serde_json::to_string(&c).unwrap(), json);
+    }
+    #[test]
+    fn test_v2_blueprint_roundtrip() {
+        use super::{
+            Container, Direction16, RequestFilters, ScheduleData, Schema, SignalType, TileRect,
+            Wire, WireConnector, WireEnd,
+        };
+        use serde_json;
+
+        let json = r#"{"blueprint":{"icons":[{"signal":{"name":"assembling-machine-3"},"index":1}],"entities":[{"entity_number":1,"name":"assembling-machine-3","position":{"x":0.5,"y":0.5},"quality":"rare","direction":4,"recipe":"electronic-circuit","recipe_quality":"uncommon","mirror":true},{"entity_number":2,"name":"constant-combinator","position":{"x":2.5,"y":0.5},"control_behavior":{"sections":{"sections":[{"index":1,"filters":[{"index":1,"name":"iron-plate","quality":"normal","comparator":"=","count":10},{"index":2,"type":"virtual","name":"signal-A","count":1}]}]}}},{"entity_number":3,"name":"requester-chest","position":{"x":3.5,"y":0.5},"request_filters":{"sections":[{"index":1,"filters":[{"index":1,"name":"copper-plate","quality":"normal","comparator":"=","count":100,"max_count":200}],"group":"copper","multiplier":2}],"trash_not_requested":true}},{"entity_number":4,"name":"elevated-straight-rail","position":{"x":7,"y":7},"direction":2},{"entity_number":5,"name":"locomotive","position":{"x":10,"y":3},"orientation":0.25}],"wires":[[2,1,3,1],[1,5,4,5]],"schedules":[{"locomotives":[5],"schedule":{"records":[{"station":"Copper","wait_conditions":[{"type":"full","compare_type":"or"}]}],"interrupts":[{"name":"Refuel","conditions":[{"type":"fuel_item_count_any","condition":{"first_signal":{"name":"coal"},"constant":10,"comparator":"<"}}],"targets":[{"station":"Fuel"}]}],"group":"copper"}}],"parameters":[{"type":"id","name":"Product","id":"parameter-0"},{"type":"number","number":"10","variable":"x"}],"item":"blueprint","version":562949954076673}}"#;
+        let c: Container = serde_json::from_str(json).unwrap();
+        match c {
+            Container::Blueprint(ref bp) => {
+                assert_eq!(bp.schema(), Schema::V2);
+                assert_eq!(bp.icons[0].signal.kind(), SignalType::Item);
+                assert_eq!(bp.entities[0].quality, Some("rare".into()));
+                assert_eq!(
+                    bp.entities[3]
+                        .direction
+                        .unwrap()
+                        .in_system(bp.direction_system()),
+                    Some(Direction16::NorthEast)
+                );
+                match bp.entities[2].request_filters {
+                    Some(RequestFilters::Sections(ref s)) => {
+                        assert_eq!(s.sections[0].group, Some("copper".into()))
+                    }
+                    ref other => panic!("expected sections, got {:?}", other),
+                }
+                assert_eq!(
+                    bp.wires[0],
+                    Wire::new(
+                        WireEnd::new(2, WireConnector::CircuitRed),
+                        WireEnd::new(3, WireConnector::CircuitRed)
+                    )
+                );
+                assert_eq!(bp.normalized_wires(), bp.wires);
+                match bp.schedules[0].schedule {
+                    ScheduleData::V2(ref data) => assert_eq!(data.interrupts.len(), 1),
+                    ref other => panic!("expected a 2.0 schedule, got {:?}", other),
+                }
+                assert_eq!(bp.schedules[0].records()[0].station, Some("Copper".into()));
+                assert_eq!(bp.parameters.len(), 2);
+                // the locomotive lies east-west, from x 7 to 13
+                assert_eq!(bp.bounding_box().unwrap(), TileRect::new(-1, -1, 13, 8));
+            }
+            _ => panic!("expected a blueprint"),
+        }
+        assert_eq!(serde_json::to_string(&c).unwrap(), json);
+    }
+    #[test]
+    fn test_legacy_connections_as_wires() {
+        use super::{Container, RequestFilters, Schema, Wire, WireConnector, WireEnd};
+        use serde_json;
+
+        let json = r#"{"blueprint":{"icons":[{"signal":{"type":"item","name":"medium-electric-pole"},"index":1}],"entities":[
+            {"entity_number":1,"name":"medium-electric-pole","position":{"x":0.5,"y":0.5},"connections":{"1":{"red":[{"entity_id":2,"circuit_id":2}]}},"neighbours":[3]},
+            {"entity_number":2,"name":"arithmetic-combinator","position":{"x":2,"y":0.5},"connections":{"2":{"red":[{"entity_id":1}]}}},
+            {"entity_number":3,"name":"medium-electric-pole","position":{"x":6.5,"y":0.5},"neighbours":[1]},
+            {"entity_number":4,"name":"power-switch","position":{"x":9,"y":1},"connections":{"Cu0":[{"entity_id":3,"wire_id":0}]}},
+            {"entity_number":5,"name":"requester-chest","position":{"x":12.5,"y":0.5},"request_filters":[{"name":"iron-plate","index":1,"count":100}]}
+        ],"item":"blueprint","version":281479275675648}}"#;
+        let c: Container = serde_json::from_str(json).unwrap();
+        let bp = match c {
+            Container::Blueprint(ref bp) => bp,
+            _ => panic!("expected a blueprint"),
+        };
+        assert_eq!(bp.schema(), Schema::Legacy);
+        match bp.entities[4].request_filters {
+            Some(RequestFilters::Legacy(ref filters)) => assert_eq!(filters[0].count, 100),
+            ref other => panic!("expected legacy filters, got {:?}", other),
+        }
+        let copper = |a, b| {
+            Wire::new(
+                WireEnd::new(a, WireConnector::PoleCopper),
+                WireEnd::new(b, WireConnector::PoleCopper),
+            )
+        };
+        assert_eq!(
+            bp.normalized_wires(),
+            vec![
+                Wire::new(
+                    WireEnd::new(1, WireConnector::CircuitRed),
+                    WireEnd::new(2, WireConnector::CombinatorOutputRed)
+                ),
+                copper(1, 3),
+                copper(4, 3),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&c).unwrap(),
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        use super::{Container, TileRect};
+        use serde_json;
+
+        // everything sits left of and above the origin - the old size() got this wrong
+        let json = r#"{"blueprint":{"entities":[{"entity_number":1,"name":"splitter","position":{"x":-4.5,"y":-3},"direction":2},{"entity_number":2,"name":"assembling-machine-1","position":{"x":-1.5,"y":-1.5}},{"entity_number":3,"name":"inserter","position":{"x":-6.5,"y":-0.5}}],"tiles":[{"name":"concrete","position":{"x":-8,"y":-8}}],"item":"blueprint","version":281479275675648}}"#;
+        let c: Container = serde_json::from_str(json).unwrap();
+        let bp = c.blueprints()[0].clone();
+        // the splitter faces east, so it's 1 wide and 2 tall
+        let bounds = bp.bounding_box().unwrap();
+        assert_eq!(bounds, TileRect::new(-8, -8, 0, 0));
+        assert_eq!((bounds.width(), bounds.height()), (8, 8));
+
+        let mut without_tiles = bp.clone();
+        without_tiles.tiles = None;
+        assert_eq!(
+            without_tiles.bounding_box().unwrap(),
+            TileRect::new(-7, -4, 0, 0)
+        );
+
+        let mut empty = bp.clone();
+        empty.entities.clear();
+        empty.tiles = None;
+        assert!(empty.bounding_box().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fixed_point_positions() {
+        use super::{Fixed, Position};
+        use serde_json;
+        use std::collections::HashSet;
+
+        let json = r#"{"x":-1.5,"y":1.19921875}"#;
+        let pos: Position = serde_json::from_str(json).unwrap();
+        assert_eq!(pos, Position::from_raw(-384, 307));
+        assert_eq!(serde_json::to_string(&pos).unwrap(), json);
+        assert_eq!(
+            serde_json::to_string(&Position::new(3.0, -0.0)).unwrap(),
+            r#"{"x":3,"y":0}"#
+        );
+
+        // negative half tiles belong to the tile below zero
+        assert_eq!(Position::new(-0.5, 0.5).tile(), (-1, 0));
+        assert_eq!(Position::new(-2.0, -1.99).tile(), (-2, -2));
+        assert!(Fixed::from_f64(-0.5).is_half());
+        assert!(Fixed::from_f64(-3.0).is_whole());
+
+        // close enough to land on the same 1/256 means the same position
+        let mut seen = HashSet::new();
+        seen.insert(Position::new(0.5, 0.5));
+        assert!(seen.contains(&Position::new(0.5000001, 0.4999999)));
+
+        assert!(serde_json::from_str::<Position>(r#"{"x":1e300,"y":0}"#).is_err());
+    }
 }
@@ -0,0 +1,376 @@
+// Checks for blueprints the game would refuse to place
+
+use blueprint::Grid;
+use entities::{EntityCategory, EntityShape, Prototypes, RotationBehaviour};
+use error::Result;
+use geo::algorithm::intersects::Intersects;
+use std::{
+    cmp,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+use types::{Blueprint, Entity, Fixed, Position};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollisionIssue {
+    // the collision boxes of two entities overlap
+    Overlap {
+        a: i32, // entity_number, a < b
+        a_position: Position,
+        b: i32,
+        b_position: Position,
+    },
+    // not lined up with everything else - e.g. a 2x2 on a tile center when the 1x1s are too
+    OffGrid {
+        entity: i32,
+        position: Position,
+    },
+    // rails are on a 2x2 grid, and this one isn't on the same one as the other rails
+    RailOffGrid {
+        entity: i32,
+        position: Position,
+    },
+    // more than one tile in the same spot - only one of them can be placed
+    TileOverlap {
+        position: Position,
+    },
+}
+
+impl fmt::Display for CollisionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CollisionIssue::Overlap {
+                a,
+                a_position,
+                b,
+                b_position,
+            } => write!(
+                f,
+                "#{} at {} overlaps #{} at {}",
+                a, a_position, b, b_position
+            ),
+            CollisionIssue::OffGrid { entity, position } => {
+                write!(f, "#{} at {} is off the tile grid", entity, position)
+            }
+            CollisionIssue::RailOffGrid { entity, position } => {
+                write!(f, "#{} at {} is off the rail grid", entity, position)
+            }
+            CollisionIssue::TileOverlap { position } => {
+                write!(f, "more than one tile at {}", position)
+            }
+        }
+    }
+}
+
+// Everything in bp that would stop the game placing it - overlaps first, then doubled up tiles,
+// then anything off the grid. Odd sized entities belong on tile centres and even ones on corners,
+// but the game shifts a whole blueprint when it's placed, so one where everything is off by the
+// same amount (0.16 wrote them half a tile over) is fine. Whatever is at its own alignment sets the
+// grid, and only if nothing is does the most common offset. Rails work the same way on their 2x2
+// grid, except that it's always down to the most common offset
+// Fails only if an entity isn't in the prototypes
+pub fn validate_collisions(bp: &Blueprint) -> Result<Vec<CollisionIssue>> {
+    validate_collisions_with(bp, Prototypes::vanilla())
+}
+
+pub fn validate_collisions_with(
+    bp: &Blueprint,
+    prototypes: &Prototypes,
+) -> Result<Vec<CollisionIssue>> {
+    let system = bp.direction_system();
+    let mut ret = Vec::new();
+
+    // only entities sharing a tile can overlap, so the grid narrows it down before the polygons
+    let grid = Grid::with_prototypes(bp, prototypes)?;
+    let mut shapes = HashMap::new();
+    for e in &bp.entities {
+        shapes.insert(
+            e.entity_number,
+            EntityShape::with_prototypes(e, system, prototypes)?,
+        );
+    }
+    let mut checked = HashSet::new();
+    for (_, here) in grid.iter() {
+        for (i, a) in here.iter().enumerate() {
+            for b in &here[i + 1..] {
+                let (a, b) = if a.entity_number <= b.entity_number {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                if !checked.insert((a.entity_number, b.entity_number)) {
+                    continue;
+                }
+                // rail pieces cross each other at every junction
+                if is_track(prototypes, &a.name) && is_track(prototypes, &b.name) {
+                    continue;
+                }
+                // trains sit on the track
+                if (is_rolling_stock(prototypes, &a.name) && is_track(prototypes, &b.name))
+                    || (is_track(prototypes, &a.name) && is_rolling_stock(prototypes, &b.name))
+                {
+                    continue;
+                }
+                // elevated rails are a layer up, everything on the ground passes under them
+                if is_elevated(&a.name) != is_elevated(&b.name) {
+                    continue;
+                }
+                if shapes[&a.entity_number]
+                    .shape
+                    .intersects(&shapes[&b.entity_number].shape)
+                {
+                    ret.push(CollisionIssue::Overlap {
+                        a: a.entity_number,
+                        a_position: a.position,
+                        b: b.entity_number,
+                        b_position: b.position,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut tiles: HashMap<Position, usize> = HashMap::new();
+    for t in bp.tiles.iter().flatten() {
+        let seen = tiles.entry(t.position).or_default();
+        *seen += 1;
+        if *seen == 2 {
+            ret.push(CollisionIssue::TileOverlap {
+                position: t.position,
+            });
+        }
+    }
+
+    // The game moves a whole blueprint at once - by whole tiles, or by whole rail cells if it has
+    // rails - so everything has to agree on where the grid is
+    let mut tile_offsets = Vec::new();
+    let mut rail_offsets = Vec::new();
+    for e in &bp.entities {
+        let proto = match prototypes.get(&e.name) {
+            Some(p) => p,
+            None => continue, // the grid would already have failed
+        };
+        if proto.rotation == RotationBehaviour::Orientation {
+            continue; // rolling stock goes wherever the rails take it
+        }
+        // odd sizes sit on tile centers, even ones on tile corners
+        let (w, h) = proto.tile_size(proto.turns(e, system));
+        let offset = |size: u32, n: Fixed, cell: i32| {
+            (n.raw() - (size as i32 % 2) * Fixed::SCALE / 2).rem_euclid(cell)
+        };
+        tile_offsets.push((
+            e,
+            (
+                offset(w, e.position.x, Fixed::SCALE),
+                offset(h, e.position.y, Fixed::SCALE),
+            ),
+        ));
+        if is_track(prototypes, &e.name) {
+            rail_offsets.push((
+                e,
+                (
+                    offset(w, e.position.x, 2 * Fixed::SCALE),
+                    offset(h, e.position.y, 2 * Fixed::SCALE),
+                ),
+            ));
+        }
+    }
+    let off_tile_grid = odd_ones_out(&tile_offsets, Some((0, 0)));
+    for e in &off_tile_grid {
+        ret.push(CollisionIssue::OffGrid {
+            entity: e.entity_number,
+            position: e.position,
+        });
+    }
+    // a rail off the tile grid has already been reported
+    rail_offsets.retain(|(e, _)| !off_tile_grid.contains(e));
+    for e in odd_ones_out(&rail_offsets, None) {
+        ret.push(CollisionIssue::RailOffGrid {
+            entity: e.entity_number,
+            position: e.position,
+        });
+    }
+    Ok(ret)
+}
+
+// everything not at the offset that's right - legal if anything is there, otherwise the most
+// common one, with a tie going to the one nearest zero
+fn odd_ones_out<'a>(
+    offsets: &[(&'a Entity, (i32, i32))],
+    legal: Option<(i32, i32)>,
+) -> Vec<&'a Entity> {
+    let mut counts: HashMap<(i32, i32), usize> = HashMap::new();
+    for &(_, offset) in offsets {
+        *counts.entry(offset).or_default() += 1;
+    }
+    let grid = match legal {
+        Some(legal) if counts.contains_key(&legal) => Some(legal),
+        _ => counts
+            .into_iter()
+            .max_by_key(|&(offset, count)| (count, cmp::Reverse(offset)))
+            .map(|(offset, _)| offset),
+    };
+    offsets
+        .iter()
+        .filter(|&&(_, offset)| Some(offset) != grid)
+        .map(|&(e, _)| e)
+        .collect()
+}
+
+// rail pieces themselves - not signals or stops
+fn is_track(prototypes: &Prototypes, name: &str) -> bool {
+    prototypes.get(name).is_some_and(|p| {
+        p.category == EntityCategory::Rail
+            && (p.rotation == RotationBehaviour::AllHeadings
+                || p.rotation == RotationBehaviour::CurvedRail)
+            && p.tile_width > 1
+    })
+}
+
+fn is_rolling_stock(prototypes: &Prototypes, name: &str) -> bool {
+    prototypes
+        .get(name)
+        .is_some_and(|p| p.rotation == RotationBehaviour::Orientation)
+}
+
+fn is_elevated(name: &str) -> bool {
+    name.starts_with("elevated-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures;
+
+    fn blueprint(entities: &str) -> Blueprint {
+        fixtures::blueprint(281479275675648, &format!(r#""entities":[{}]"#, entities))
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let bp = blueprint(
+            r#"{"entity_number":1,"name":"assembling-machine-1","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"inserter","position":{"x":1.5,"y":0.5}},
+            {"entity_number":3,"name":"transport-belt","position":{"x":2.5,"y":0.5}},
+            {"entity_number":4,"name":"splitter","position":{"x":3,"y":1.5}}"#,
+        );
+        let issues = validate_collisions(&bp).unwrap();
+        assert_eq!(
+            issues,
+            vec![CollisionIssue::Overlap {
+                a: 1,
+                a_position: Position::new(0.5, 0.5),
+                b: 2,
+                b_position: Position::new(1.5, 0.5),
+            }]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "#1 at (0.5,0.5) overlaps #2 at (1.5,0.5)"
+        );
+    }
+
+    #[test]
+    fn test_off_grid() {
+        let bp = blueprint(
+            r#"{"entity_number":1,"name":"stone-furnace","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"transport-belt","position":{"x":5,"y":5.5}},
+            {"entity_number":3,"name":"splitter","position":{"x":8.5,"y":9},"direction":2},
+            {"entity_number":4,"name":"straight-rail","position":{"x":11,"y":11}},
+            {"entity_number":5,"name":"straight-rail","position":{"x":11,"y":13}},
+            {"entity_number":6,"name":"straight-rail","position":{"x":14,"y":13}},
+            {"entity_number":7,"name":"cargo-wagon","position":{"x":20.3,"y":1.7},"orientation":0.1}"#,
+        );
+        let issues = validate_collisions(&bp).unwrap();
+        assert_eq!(
+            issues,
+            vec![
+                CollisionIssue::OffGrid {
+                    entity: 1,
+                    position: Position::new(0.5, 0.5)
+                },
+                CollisionIssue::OffGrid {
+                    entity: 2,
+                    position: Position::new(5.0, 5.5)
+                },
+                CollisionIssue::RailOffGrid {
+                    entity: 6,
+                    position: Position::new(14.0, 13.0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_off_grid_prefers_the_right_alignment() {
+        // two belts agree with each other, but the one on a tile centre is where belts go
+        let bp = blueprint(
+            r#"{"entity_number":1,"name":"transport-belt","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"transport-belt","position":{"x":2,"y":2}},
+            {"entity_number":3,"name":"transport-belt","position":{"x":3,"y":2}}"#,
+        );
+        assert_eq!(
+            validate_collisions(&bp).unwrap(),
+            vec![
+                CollisionIssue::OffGrid {
+                    entity: 2,
+                    position: Position::new(2.0, 2.0)
+                },
+                CollisionIssue::OffGrid {
+                    entity: 3,
+                    position: Position::new(3.0, 2.0)
+                },
+            ]
+        );
+
+        // with nothing to go by, all of them being off the same way is fine
+        let mut shifted = bp.clone();
+        shifted.entities.remove(0);
+        assert_eq!(validate_collisions(&shifted).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_train_on_rails() {
+        let bp = blueprint(
+            r#"{"entity_number":1,"name":"straight-rail","position":{"x":1,"y":1}},
+            {"entity_number":2,"name":"straight-rail","position":{"x":1,"y":3}},
+            {"entity_number":3,"name":"straight-rail","position":{"x":1,"y":5}},
+            {"entity_number":4,"name":"locomotive","position":{"x":1,"y":3},"orientation":0},
+            {"entity_number":5,"name":"rail-signal","position":{"x":2.5,"y":3.5}}"#,
+        );
+        assert_eq!(validate_collisions(&bp).unwrap(), vec![]);
+
+        // but a chest on the track still blocks both
+        let mut blocked = bp.clone();
+        blocked.entities[4].name = "wooden-chest".into();
+        blocked.entities[4].position = Position::new(1.5, 3.5);
+        assert_eq!(
+            validate_collisions(&blocked).unwrap(),
+            vec![
+                CollisionIssue::Overlap {
+                    a: 2,
+                    a_position: Position::new(1.0, 3.0),
+                    b: 5,
+                    b_position: Position::new(1.5, 3.5),
+                },
+                CollisionIssue::Overlap {
+                    a: 4,
+                    a_position: Position::new(1.0, 3.0),
+                    b: 5,
+                    b_position: Position::new(1.5, 3.5),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_balancer_is_clean() {
+        use blueprint::read_blueprint;
+        use fixtures::balancer;
+
+        // 0.16 put everything half a tile over from where 1.x would, which is fine as long as it all agrees
+        let c = read_blueprint(&balancer()).unwrap();
+        let bp = c.blueprints()[0];
+        assert_eq!(validate_collisions(bp).unwrap(), vec![]);
+    }
+}